@@ -1,9 +1,12 @@
 use crate::{
     config::*,
-    core::{tasks::jobs::ProfilesJobGuard, updater::ManifestVersionLatest, *},
-    feat, ret_err,
+    core::{
+        revisions::RevisionSource, tasks::jobs::ProfilesJobGuard, updater::ManifestVersionLatest, *,
+    },
+    enhance::{self, EnhanceTrace, ValidationError},
+    feat, log_err, ret_err,
     utils::{
-        candy, dirs, help,
+        candy, crypto, dirs, help,
         resolve::{self, save_window_state},
     },
     wrap_err,
@@ -12,7 +15,10 @@ use anyhow::{Context, Result};
 use chrono::Local;
 use log::debug;
 use serde_yaml::Mapping;
-use std::collections::{HashMap, VecDeque};
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+};
 use sysproxy::Sysproxy;
 
 use tauri::api::dialog::FileDialogBuilder;
@@ -44,15 +50,34 @@ pub async fn enhance_profiles() -> CmdResult {
 }
 
 #[tauri::command]
-pub async fn import_profile(url: String, option: Option<PrfOption>) -> CmdResult {
+pub async fn import_profile(
+    url: String,
+    option: Option<PrfOption>,
+) -> CmdResult<Vec<(String, String)>> {
     let item = wrap_err!(PrfItem::from_url(&url, None, None, option).await)?;
-    wrap_err!(Config::profiles().data().append_item(item))
+    append_profile(item)
 }
 
 #[tauri::command]
-pub async fn create_profile(item: PrfItem, file_data: Option<String>) -> CmdResult {
+pub async fn create_profile(
+    item: PrfItem,
+    file_data: Option<String>,
+) -> CmdResult<Vec<(String, String)>> {
     let item = wrap_err!(PrfItem::from(item, file_data).await)?;
-    wrap_err!(Config::profiles().data().append_item(item))
+    append_profile(item)
+}
+
+/// append the new item and keep the first revision,
+/// return the logs of the conversion
+fn append_profile(mut item: PrfItem) -> CmdResult<Vec<(String, String)>> {
+    let logs = item.convert_logs.take().unwrap_or_default();
+    let revision = item.uid.clone().zip(item.file_data.clone());
+    wrap_err!(Config::profiles().data().append_item(item))?;
+    if let Some((uid, data)) = revision {
+        let revisions = revisions::ProfileRevisions::global();
+        log_err!(revisions.record(&uid, RevisionSource::Import, &data));
+    }
+    Ok(logs)
 }
 
 #[tauri::command]
@@ -61,13 +86,18 @@ pub async fn reorder_profile(active_id: String, over_id: String) -> CmdResult {
 }
 
 #[tauri::command]
-pub async fn update_profile(index: String, option: Option<PrfOption>) -> CmdResult {
+pub async fn update_profile(
+    index: String,
+    option: Option<PrfOption>,
+) -> CmdResult<Option<ProfileChanges>> {
     wrap_err!(feat::update_profile(index, option).await)
 }
 
 #[tauri::command]
 pub async fn delete_profile(index: String) -> CmdResult {
-    let should_update = wrap_err!({ Config::profiles().data().delete_item(index) })?;
+    let should_update = wrap_err!({ Config::profiles().data().delete_item(index.clone()) })?;
+    // the history is kept if the profile failed to be deleted
+    log_err!(revisions::ProfileRevisions::global().clear(&index));
     if should_update {
         wrap_err!(CoreManager::global().update_config().await)?;
         handle::Handle::refresh_clash();
@@ -139,7 +169,30 @@ pub fn save_profile_file(index: String, file_data: Option<String>) -> CmdResult
     let profiles = Config::profiles();
     let profiles = profiles.latest();
     let item = wrap_err!(profiles.get_item(&index))?;
-    wrap_err!(item.save_file(file_data.unwrap()))
+    let file_data = file_data.unwrap();
+
+    let revisions = revisions::ProfileRevisions::global();
+    log_err!(item
+        .read_file()
+        .and_then(|old| revisions.record_base(&index, &old)));
+    wrap_err!(item.save_file(file_data.clone()))?;
+    log_err!(revisions.record(&index, RevisionSource::ManualEdit, &file_data));
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_profile_revisions(index: String) -> CmdResult<Vec<revisions::ProfileRevision>> {
+    wrap_err!(revisions::ProfileRevisions::global().list(&index))
+}
+
+#[tauri::command]
+pub fn get_profile_revision_diff(index: String, from: u64, to: u64) -> CmdResult<String> {
+    wrap_err!(revisions::ProfileRevisions::global().diff(&index, from, to))
+}
+
+#[tauri::command]
+pub async fn restore_profile_revision(index: String, id: u64) -> CmdResult {
+    wrap_err!(feat::restore_profile_revision(index, id).await)
 }
 
 #[tauri::command]
@@ -164,6 +217,29 @@ pub fn get_runtime_yaml() -> CmdResult<String> {
         ))
 }
 
+#[tauri::command]
+pub fn get_runtime_validation() -> CmdResult<Vec<ValidationError>> {
+    Ok(Config::runtime().latest().validation.clone())
+}
+
+/// run the enhancement without applying it, return the config after every stage
+#[tauri::command]
+pub async fn dry_run_enhance() -> CmdResult<EnhanceTrace> {
+    wrap_err!(tokio::task::spawn_blocking(enhance::dry_run).await)
+}
+
+/// export the runtime config for the other clients
+#[tauri::command]
+pub fn get_runtime_export(format: convert::ExportFormat) -> CmdResult<convert::ExportResult> {
+    let runtime = Config::runtime();
+    let runtime = runtime.latest();
+    let config = runtime.config.as_ref();
+    match config {
+        Some(config) => Ok(convert::export_config(config, format)),
+        None => ret_err!("failed to get the runtime config"),
+    }
+}
+
 #[tauri::command]
 pub fn get_runtime_exists() -> CmdResult<Vec<String>> {
     Ok(Config::runtime().latest().exists_keys.clone())
@@ -304,6 +380,40 @@ pub async fn collect_logs() -> CmdResult {
     Ok(())
 }
 
+/// export the app state to a zip bundle, the secrets are encrypted with the `passphrase`
+#[tauri::command]
+pub fn create_backup(path: String, with_storage: bool, passphrase: Option<String>) -> CmdResult {
+    wrap_err!(backup::create_backup(
+        Path::new(&path),
+        with_storage,
+        passphrase.as_deref()
+    ))
+}
+
+#[tauri::command]
+pub async fn restore_backup(
+    path: String,
+    mode: backup::RestoreMode,
+    passphrase: Option<String>,
+) -> CmdResult {
+    wrap_err!(backup::restore_backup(Path::new(&path), mode, passphrase.as_deref()).await)
+}
+
+/// provide the passphrase of the secrets when no keyring is available
+#[tauri::command]
+pub fn unlock_secrets(passphrase: String) -> CmdResult {
+    wrap_err!(crypto::set_passphrase(&passphrase))?;
+    Config::profiles().data().decrypt_secrets();
+    Config::clash().data().decrypt_secrets();
+    Ok(())
+}
+
+/// sync with the WebDAV server now, `force` resolves the conflict
+#[tauri::command]
+pub async fn webdav_sync(force: Option<webdav::SyncAction>) -> CmdResult<webdav::SyncAction> {
+    wrap_err!(webdav::sync(force).await)
+}
+
 #[tauri::command]
 pub async fn update_core(core_type: shadowrocket::ClashCore) -> CmdResult {
     wrap_err!(
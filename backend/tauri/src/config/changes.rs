@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::{HashMap, HashSet};
+
+/// the changes of the named items, e.g. `proxies` and `proxy-groups`
+#[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct NamedChanges {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct RuleChanges {
+    pub before: usize,
+    pub after: usize,
+    pub added: usize,
+    pub removed: usize,
+}
+
+/// the changes of a profile between two updates
+#[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ProfileChanges {
+    pub proxies: NamedChanges,
+    pub proxy_groups: NamedChanges,
+    pub rules: RuleChanges,
+}
+
+impl NamedChanges {
+    fn diff(old: Option<&Value>, new: Option<&Value>) -> Self {
+        let old = named_items(old);
+        let new = named_items(new);
+        let mut changes = Self::default();
+
+        for (name, item) in new.iter() {
+            match old.get(name) {
+                Some(old_item) if old_item != item => changes.modified.push(name.to_string()),
+                Some(_) => {}
+                None => changes.added.push(name.to_string()),
+            }
+        }
+        for name in old.keys().filter(|name| !new.contains_key(*name)) {
+            changes.removed.push(name.to_string());
+        }
+
+        changes.added.sort();
+        changes.removed.sort();
+        changes.modified.sort();
+        changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+
+    fn summary(&self) -> String {
+        format!(
+            "+{} -{} ~{}",
+            self.added.len(),
+            self.removed.len(),
+            self.modified.len()
+        )
+    }
+}
+
+impl RuleChanges {
+    fn diff(old: Option<&Value>, new: Option<&Value>) -> Self {
+        let old = rule_list(old);
+        let new = rule_list(new);
+        let old_set: HashSet<&str> = old.iter().copied().collect();
+        let new_set: HashSet<&str> = new.iter().copied().collect();
+
+        Self {
+            before: old.len(),
+            after: new.len(),
+            added: new_set.difference(&old_set).count(),
+            removed: old_set.difference(&new_set).count(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.before == self.after && self.added == 0 && self.removed == 0
+    }
+}
+
+impl ProfileChanges {
+    /// compare the `proxies`, `proxy-groups` and `rules` of two profiles
+    pub fn diff(old: &Mapping, new: &Mapping) -> Self {
+        Self {
+            proxies: NamedChanges::diff(old.get("proxies"), new.get("proxies")),
+            proxy_groups: NamedChanges::diff(old.get("proxy-groups"), new.get("proxy-groups")),
+            rules: RuleChanges::diff(old.get("rules"), new.get("rules")),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.proxies.is_empty() && self.proxy_groups.is_empty() && self.rules.is_empty()
+    }
+
+    /// a short summary for the notification
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "proxies {}, groups {}, rules {} -> {}",
+            self.proxies.summary(),
+            self.proxy_groups.summary(),
+            self.rules.before,
+            self.rules.after
+        );
+        if !self.proxies.removed.is_empty() {
+            summary.push_str(&format!(
+                "; removed proxies: {}",
+                self.proxies.removed.join(", ")
+            ));
+        }
+        summary
+    }
+}
+
+fn named_items(value: Option<&Value>) -> HashMap<&str, &Value> {
+    value
+        .and_then(Value::as_sequence)
+        .map(|seq| {
+            seq.iter()
+                .filter_map(|item| Some((item.get("name")?.as_str()?, item)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn rule_list(value: Option<&Value>) -> Vec<&str> {
+    value
+        .and_then(Value::as_sequence)
+        .map(|seq| seq.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default()
+}
+
+#[test]
+fn test_profile_changes() -> anyhow::Result<()> {
+    let old = r#"
+proxies:
+  - { name: hk, type: ss, server: hk.example.com, port: 8388 }
+  - { name: jp, type: ss, server: jp.example.com, port: 8388 }
+  - { name: us, type: ss, server: us.example.com, port: 8388 }
+proxy-groups:
+  - { name: PROXY, type: select, proxies: [hk, jp, us] }
+rules:
+  - DOMAIN-SUFFIX,google.com,PROXY
+  - MATCH,PROXY
+"#;
+    let new = r#"
+proxies:
+  - { name: hk, type: ss, server: hk.example.com, port: 8388 }
+  - { name: jp, type: ss, server: jp2.example.com, port: 8388 }
+  - { name: sg, type: ss, server: sg.example.com, port: 8388 }
+proxy-groups:
+  - { name: PROXY, type: select, proxies: [hk, jp, sg] }
+rules:
+  - DOMAIN-SUFFIX,github.com,PROXY
+  - DOMAIN-SUFFIX,google.com,PROXY
+  - MATCH,PROXY
+"#;
+    let old = serde_yaml::from_str::<Mapping>(old)?;
+    let new = serde_yaml::from_str::<Mapping>(new)?;
+    let changes = ProfileChanges::diff(&old, &new);
+
+    assert_eq!(changes.proxies.added, vec!["sg"]);
+    assert_eq!(changes.proxies.removed, vec!["us"]);
+    assert_eq!(changes.proxies.modified, vec!["jp"]);
+    assert_eq!(changes.proxy_groups.modified, vec!["PROXY"]);
+    assert_eq!(
+        changes.rules,
+        RuleChanges {
+            before: 2,
+            after: 3,
+            added: 1,
+            removed: 0
+        }
+    );
+    assert!(!changes.is_empty());
+    assert!(ProfileChanges::diff(&old, &old).is_empty());
+    Ok(())
+}
@@ -1,5 +1,5 @@
 use crate::utils::{
-    dirs,
+    crypto, dirs,
     help::{self, get_clash_external_port},
 };
 use anyhow::Result;
@@ -20,7 +20,11 @@ pub struct IClashTemp(pub Mapping);
 impl IClashTemp {
     pub fn new() -> Self {
         match dirs::clash_path().and_then(|path| help::read_merge_mapping(&path)) {
-            Ok(map) => Self(Self::guard(map)),
+            Ok(map) => {
+                let mut config = Self(Self::guard(map));
+                config.decrypt_secrets();
+                config
+            }
             Err(err) => {
                 log::error!(target: "app", "{err}");
                 Self::template()
@@ -52,7 +56,7 @@ impl IClashTemp {
         Self(map)
     }
 
-    fn guard(mut config: Mapping) -> Mapping {
+    pub fn guard(mut config: Mapping) -> Mapping {
         let port = Self::guard_mixed_port(&config);
         let ctrl = Self::guard_server_ctrl(&config);
 
@@ -68,13 +72,33 @@ impl IClashTemp {
     }
 
     pub fn save_config(&self) -> Result<()> {
+        let mut config = self.clone();
+        config.encrypt_secrets()?;
         help::save_yaml(
             &dirs::clash_path()?,
-            &self.0,
+            &config.0,
             Some("# Generated by Shadowrocket"),
         )
     }
 
+    /// encrypt the controller secret if the encryption is enabled
+    pub fn encrypt_secrets(&mut self) -> Result<()> {
+        if let Some(Value::String(secret)) = self.0.get_mut("secret") {
+            *secret = crypto::encrypt(secret)?;
+        }
+        Ok(())
+    }
+
+    /// decrypt the controller secret, keep it as it is if failed
+    pub fn decrypt_secrets(&mut self) {
+        if let Some(Value::String(secret)) = self.0.get_mut("secret") {
+            match crypto::decrypt(secret) {
+                Ok(plain) => *secret = plain,
+                Err(err) => log::error!(target: "app", "failed to decrypt the secret: {err}"),
+            }
+        }
+    }
+
     pub fn get_mixed_port(&self) -> u16 {
         Self::guard_mixed_port(&self.0)
     }
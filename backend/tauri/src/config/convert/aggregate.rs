@@ -0,0 +1,190 @@
+//! Combine the proxies of several profiles into one
+use super::{
+    build_profile, region_pattern, unique_name, ConvertLogs, DEFAULT_SELECT_GROUP,
+    DEFAULT_TEST_URL, REGIONS,
+};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Sequence, Value};
+use std::collections::HashSet;
+
+/// an `url-test` group of the proxies whose name matches the pattern
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct RegionGroup {
+    pub name: String,
+    pub pattern: String,
+}
+
+impl RegionGroup {
+    fn new(name: &str, pattern: &str) -> Self {
+        Self {
+            name: name.into(),
+            pattern: pattern.into(),
+        }
+    }
+
+    /// used when the aggregate profile does not specify any region,
+    /// the groups without any member are not generated
+    pub fn defaults() -> Vec<Self> {
+        REGIONS
+            .iter()
+            .map(|(name, flag, pattern)| Self::new(name, &region_pattern(flag, pattern)))
+            .collect()
+    }
+}
+
+/// combine the `proxies` of the sources, `(source name, profile)`,
+/// the names are kept unique and the identical servers could be removed
+pub fn aggregate_proxies(
+    sources: Vec<(String, Mapping)>,
+    prefix_name: bool,
+    dedupe: bool,
+) -> (Sequence, ConvertLogs) {
+    let mut proxies = Sequence::new();
+    let mut logs = ConvertLogs::new();
+    let mut names = HashSet::new();
+    let mut servers = HashSet::new();
+
+    for (source, profile) in sources {
+        let list = profile.get("proxies").and_then(Value::as_sequence);
+        let Some(list) = list.filter(|l| !l.is_empty()) else {
+            logs.push((
+                "warn".into(),
+                format!("`{source}` does not contain any proxy"),
+            ));
+            continue;
+        };
+
+        for proxy in list.iter().filter_map(Value::as_mapping) {
+            let mut proxy = proxy.clone();
+            let Some(name) = proxy
+                .remove("name")
+                .and_then(|n| n.as_str().map(String::from))
+            else {
+                continue;
+            };
+
+            // the proxies are identical except the name
+            if dedupe {
+                let server = serde_yaml::to_string(&proxy).unwrap_or_default();
+                if !servers.insert(server) {
+                    logs.push((
+                        "info".into(),
+                        format!("`{name}` of `{source}` is a duplicate, skipped"),
+                    ));
+                    continue;
+                }
+            }
+
+            let name = match prefix_name {
+                true => format!("{source} | {name}"),
+                false => name,
+            };
+            proxy.insert("name".into(), unique_name(&mut names, name).into());
+            proxies.push(proxy.into());
+        }
+    }
+
+    (proxies, logs)
+}
+
+/// build the profile with the proxies, the region groups are
+/// added to the `PROXY` group if any proxy matches
+pub fn build_aggregate_profile(
+    proxies: Sequence,
+    regions: &[RegionGroup],
+) -> (Mapping, ConvertLogs) {
+    let mut logs = ConvertLogs::new();
+    let names = super::proxy_names(&proxies);
+
+    let mut groups = vec![];
+    for region in regions {
+        let regex = match Regex::new(&region.pattern) {
+            Ok(regex) => regex,
+            Err(err) => {
+                logs.push((
+                    "warn".into(),
+                    format!("invalid pattern of region `{}`: {err}", region.name),
+                ));
+                continue;
+            }
+        };
+        let members = names
+            .iter()
+            .filter(|n| regex.is_match(n))
+            .cloned()
+            .collect::<Vec<_>>();
+        if members.is_empty() {
+            continue;
+        }
+
+        let mut group = Mapping::new();
+        group.insert("name".into(), region.name.as_str().into());
+        group.insert("type".into(), "url-test".into());
+        group.insert("url".into(), DEFAULT_TEST_URL.into());
+        group.insert("interval".into(), 300.into());
+        group.insert("proxies".into(), members.into());
+        groups.push(group);
+    }
+
+    let mut profile = build_profile(proxies);
+    let Some(Value::Sequence(list)) = profile.get_mut("proxy-groups") else {
+        return (profile, logs);
+    };
+
+    // PROXY: [AUTO, ...regions, ...proxies]
+    let region_names = groups
+        .iter()
+        .filter_map(|g| g.get("name").cloned())
+        .collect::<Vec<_>>();
+    for group in list.iter_mut() {
+        if group.get("name").and_then(Value::as_str) != Some(DEFAULT_SELECT_GROUP) {
+            continue;
+        }
+        if let Some(Value::Sequence(members)) = group.get_mut("proxies") {
+            for (i, name) in region_names.iter().enumerate() {
+                members.insert(i + 1, name.clone());
+            }
+        }
+    }
+    list.extend(groups.into_iter().map(Value::Mapping));
+
+    (profile, logs)
+}
+
+#[test]
+fn test_aggregate() -> anyhow::Result<()> {
+    let a = r#"
+proxies:
+  - { name: 🇭🇰 HK 01, type: ss, server: hk.example.com, port: 8388 }
+  - { name: 🇯🇵 JP 01, type: ss, server: jp.example.com, port: 8388 }
+"#;
+    let b = r#"
+proxies:
+  - { name: 香港 01, type: ss, server: hk.example.com, port: 8388 }
+  - { name: 🇯🇵 JP 01, type: ss, server: jp.example.net, port: 8388 }
+"#;
+    let sources = vec![
+        ("a".to_string(), serde_yaml::from_str::<Mapping>(a)?),
+        ("b".to_string(), serde_yaml::from_str::<Mapping>(b)?),
+        ("c".to_string(), Mapping::new()),
+    ];
+
+    let (proxies, logs) = aggregate_proxies(sources.clone(), false, true);
+    let names = super::proxy_names(&proxies);
+    assert_eq!(names, vec!["🇭🇰 HK 01", "🇯🇵 JP 01", "🇯🇵 JP 01 2"]);
+    assert_eq!(logs.len(), 2);
+
+    let (proxies, _) = aggregate_proxies(sources, true, false);
+    let names = super::proxy_names(&proxies);
+    assert_eq!(names[2], "b | 香港 01");
+
+    let (profile, logs) = build_aggregate_profile(proxies, &RegionGroup::defaults());
+    assert!(logs.is_empty());
+    let groups = profile["proxy-groups"].as_sequence().unwrap();
+    assert_eq!(groups.len(), 4);
+    assert_eq!(groups[0]["proxies"][1], Value::from("HK"));
+    assert_eq!(groups[0]["proxies"][2], Value::from("JP"));
+    assert_eq!(groups[2]["proxies"].as_sequence().unwrap().len(), 2);
+    Ok(())
+}
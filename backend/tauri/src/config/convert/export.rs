@@ -0,0 +1,941 @@
+//! Export the clash `Mapping` as the Shadowrocket / Surge / Quantumult X config
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Shadowrocket,
+    Surge,
+    #[serde(rename = "quantumultx")]
+    QuantumultX,
+}
+
+impl std::fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportFormat::Shadowrocket => write!(f, "Shadowrocket"),
+            ExportFormat::Surge => write!(f, "Surge"),
+            ExportFormat::QuantumultX => write!(f, "Quantumult X"),
+        }
+    }
+}
+
+/// the item could not be expressed in the target format
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ExportSkipped {
+    /// enum value: proxy | proxy-group | rule
+    pub kind: String,
+    pub name: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct ExportResult {
+    pub data: String,
+    pub skipped: Vec<ExportSkipped>,
+}
+
+/// export the clash config to the target format,
+/// the skipped items are also written as comments on the top of the data
+pub fn export_config(config: &Mapping, format: ExportFormat) -> ExportResult {
+    let mut exporter = Exporter::new(config, format);
+    let body = match format {
+        ExportFormat::Shadowrocket | ExportFormat::Surge => exporter.surge(),
+        ExportFormat::QuantumultX => exporter.quanx(),
+    };
+
+    let mut data = format!("# Exported for {format}\n");
+    for item in exporter.skipped.iter() {
+        data.push_str(&format!(
+            "# [skipped] {} `{}`: {}\n",
+            item.kind, item.name, item.reason
+        ));
+    }
+    data.push('\n');
+    data.push_str(&body);
+
+    ExportResult {
+        data,
+        skipped: exporter.skipped,
+    }
+}
+
+/// the builtin policies could be expressed in all the formats
+const BUILTIN_POLICIES: [&str; 3] = ["DIRECT", "REJECT", "REJECT-DROP"];
+
+/// the rule types share the same syntax in Surge
+const SURGE_RULES: [&str; 12] = [
+    "DOMAIN",
+    "DOMAIN-SUFFIX",
+    "DOMAIN-KEYWORD",
+    "GEOIP",
+    "IP-CIDR",
+    "IP-CIDR6",
+    "IP-ASN",
+    "SRC-PORT",
+    "IN-PORT",
+    "PROCESS-NAME",
+    "AND",
+    "OR",
+];
+
+struct Exporter<'a> {
+    config: &'a Mapping,
+    format: ExportFormat,
+    /// the exported proxies and groups, `(name, line)`
+    proxies: Vec<(String, String)>,
+    groups: Vec<(&'a Mapping, Vec<String>)>,
+    /// the policy names could be referenced
+    available: HashSet<String>,
+    skipped: Vec<ExportSkipped>,
+}
+
+/// read the field as string, numbers and bools are accepted
+fn field(map: &Mapping, key: &str) -> Option<String> {
+    match map.get(key)? {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn flag(map: &Mapping, key: &str) -> bool {
+    map.get(key).and_then(Value::as_bool).unwrap_or(false)
+}
+
+fn sub_map<'a>(map: &'a Mapping, key: &str) -> Option<&'a Mapping> {
+    map.get(key).and_then(Value::as_mapping)
+}
+
+fn names<'a>(map: &'a Mapping, key: &str) -> Vec<&'a str> {
+    map.get(key)
+        .and_then(Value::as_sequence)
+        .map(|s| s.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default()
+}
+
+/// the `,` and `=` in the names would break the config lines
+fn check_name(name: &str) -> Result<()> {
+    if name.contains(',') || name.contains('=') {
+        bail!("the name contains `,` or `=`");
+    }
+    Ok(())
+}
+
+/// `ws-opts.headers.Host` or the other headers
+fn ws_host(proxy: &Mapping) -> Option<String> {
+    let headers = sub_map(proxy, "ws-opts").and_then(|o| sub_map(o, "headers"))?;
+    field(headers, "Host").or(field(headers, "host"))
+}
+
+fn ws_path(proxy: &Mapping) -> String {
+    sub_map(proxy, "ws-opts")
+        .and_then(|o| field(o, "path"))
+        .unwrap_or("/".into())
+}
+
+impl<'a> Exporter<'a> {
+    fn new(config: &'a Mapping, format: ExportFormat) -> Self {
+        Self {
+            config,
+            format,
+            proxies: vec![],
+            groups: vec![],
+            available: HashSet::new(),
+            skipped: vec![],
+        }
+    }
+
+    fn skip<S: Into<String>>(&mut self, kind: &str, name: &str, reason: S) {
+        self.skipped.push(ExportSkipped {
+            kind: kind.into(),
+            name: name.into(),
+            reason: reason.into(),
+        });
+    }
+
+    fn seq(&self, key: &str) -> Vec<&'a Mapping> {
+        self.config
+            .get(key)
+            .and_then(Value::as_sequence)
+            .map(|s| s.iter().filter_map(Value::as_mapping).collect())
+            .unwrap_or_default()
+    }
+
+    /// convert the clash policy name to the target one
+    fn policy(&self, name: &str) -> String {
+        match (self.format, name) {
+            (ExportFormat::QuantumultX, "DIRECT") => "direct".into(),
+            (ExportFormat::QuantumultX, "REJECT" | "REJECT-DROP") => "reject".into(),
+            (_, "REJECT-DROP") => "REJECT-DROP".into(),
+            (_, name) => name.into(),
+        }
+    }
+
+    /// export the proxies and groups,
+    /// the groups without any available member are skipped recursively
+    fn prepare(&mut self, proxy_line: fn(&Self, &Mapping) -> Result<String>) {
+        for proxy in self.seq("proxies") {
+            let name = field(proxy, "name").unwrap_or_default();
+            match check_name(&name).and_then(|_| proxy_line(self, proxy)) {
+                Ok(line) => {
+                    self.available.insert(name.clone());
+                    self.proxies.push((name, line));
+                }
+                Err(err) => self.skip("proxy", &name, err.to_string()),
+            }
+        }
+
+        self.available
+            .extend(BUILTIN_POLICIES.iter().map(|s| s.to_string()));
+
+        let mut groups = vec![];
+        for group in self.seq("proxy-groups") {
+            let name = field(group, "name").unwrap_or_default();
+            match check_name(&name) {
+                Ok(_) => {
+                    self.available.insert(name);
+                    groups.push(group);
+                }
+                Err(err) => self.skip("proxy-group", &name, err.to_string()),
+            }
+        }
+
+        loop {
+            let mut changed = false;
+            for group in groups.iter() {
+                let name = field(group, "name").unwrap_or_default();
+                if !self.available.contains(&name) {
+                    continue;
+                }
+                let has_member = names(group, "proxies")
+                    .iter()
+                    .any(|m| self.available.contains(*m));
+                let has_others = group.contains_key("use") || flag(group, "include-all");
+                if !has_member && !has_others {
+                    self.available.remove(&name);
+                    self.skip("proxy-group", &name, "no available member");
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        for group in groups {
+            let name = field(group, "name").unwrap_or_default();
+            if !self.available.contains(&name) {
+                continue;
+            }
+            let mut members = vec![];
+            for member in names(group, "proxies") {
+                match self.available.contains(member) {
+                    true => members.push(self.policy(member)),
+                    false => self.skip(
+                        "proxy-group",
+                        &name,
+                        format!("member `{member}` is not available"),
+                    ),
+                }
+            }
+            self.groups.push((group, members));
+        }
+    }
+
+    /// the http provider's url of the group
+    fn provider_url(&mut self, group: &Mapping, name: &str) -> Option<String> {
+        let providers = names(group, "use");
+        if providers.is_empty() {
+            return None;
+        }
+        if providers.len() > 1 {
+            self.skip(
+                "proxy-group",
+                name,
+                "only the first proxy provider is exported",
+            );
+        }
+        let provider = self
+            .config
+            .get("proxy-providers")
+            .and_then(Value::as_mapping)
+            .and_then(|p| sub_map(p, providers[0]));
+        match provider.and_then(|p| field(p, "url")) {
+            Some(url) => Some(url),
+            None => {
+                self.skip(
+                    "proxy-group",
+                    name,
+                    format!("proxy provider `{}` is not a remote one", providers[0]),
+                );
+                None
+            }
+        }
+    }
+
+    /// the remote text rule provider's url
+    fn rule_provider(&self, name: &str) -> Result<(String, String)> {
+        let provider = self
+            .config
+            .get("rule-providers")
+            .and_then(Value::as_mapping)
+            .and_then(|p| sub_map(p, name));
+        let Some(provider) = provider else {
+            bail!("rule provider `{name}` is not defined");
+        };
+        let Some(url) = field(provider, "url") else {
+            bail!("rule provider `{name}` is not a remote one");
+        };
+        if field(provider, "format").as_deref() != Some("text") {
+            bail!("rule provider `{name}` is not in the text format");
+        }
+        let behavior = field(provider, "behavior").unwrap_or("classical".into());
+        Ok((url, behavior))
+    }
+
+    /// split the rule into the (type, payload, policy, no-resolve)
+    fn split_rule(rule: &str) -> Result<(String, String, String, bool)> {
+        let (rule, no_resolve) = match rule.strip_suffix(",no-resolve") {
+            Some(rule) => (rule, true),
+            None => (rule, false),
+        };
+        let (rtype, rest) = rule.split_once(',').unwrap_or((rule, ""));
+        let (payload, policy) = match rest.rsplit_once(',') {
+            Some((payload, policy)) => (payload, policy),
+            None if rtype == "MATCH" => ("", rest),
+            None => bail!("malformed rule"),
+        };
+        Ok((
+            rtype.trim().to_ascii_uppercase(),
+            payload.trim().into(),
+            policy.trim().into(),
+            no_resolve,
+        ))
+    }
+
+    fn rules(&self) -> Vec<&'a str> {
+        names(self.config, "rules")
+    }
+
+    /// ## Shadowrocket / Surge
+    fn surge(&mut self) -> String {
+        self.prepare(Self::surge_proxy);
+
+        let mut general = vec![];
+        if let Some(ipv6) = self.config.get("ipv6").and_then(Value::as_bool) {
+            general.push(format!("ipv6 = {ipv6}"));
+        }
+        let dns = sub_map(self.config, "dns");
+        let nameservers = dns.map(|d| names(d, "nameserver")).unwrap_or_default();
+        let (encrypted, plain): (Vec<&str>, Vec<&str>) = nameservers
+            .into_iter()
+            .partition(|s| s.starts_with("https://") || s.starts_with("quic://"));
+        let plain = plain
+            .into_iter()
+            .filter(|s| !s.contains("://") || s.starts_with("udp://"))
+            .map(|s| s.trim_start_matches("udp://"))
+            .collect::<Vec<_>>();
+        if !plain.is_empty() {
+            general.push(format!("dns-server = {}", plain.join(", ")));
+        }
+        if !encrypted.is_empty() {
+            general.push(format!("encrypted-dns-server = {}", encrypted.join(", ")));
+        }
+
+        let mut groups = vec![];
+        for (group, members) in std::mem::take(&mut self.groups) {
+            let name = field(group, "name").unwrap_or_default();
+            let gtype = field(group, "type").unwrap_or_default();
+            let gtype = match gtype.as_str() {
+                "select" | "url-test" | "fallback" | "load-balance" => gtype,
+                gtype => {
+                    self.skip(
+                        "proxy-group",
+                        &name,
+                        format!("`{gtype}` is converted to `select`"),
+                    );
+                    "select".into()
+                }
+            };
+            let mut parts = vec![gtype.clone()];
+            parts.extend(members);
+
+            if let Some(url) = self.provider_url(group, &name) {
+                parts.push(format!("policy-path={url}"));
+            }
+            if flag(group, "include-all") {
+                parts.push("include-all-proxies=true".into());
+            }
+            if let Some(filter) = field(group, "filter") {
+                parts.push(format!("policy-regex-filter={filter}"));
+            }
+            if gtype != "select" {
+                if let Some(url) = field(group, "url") {
+                    parts.push(format!("url={url}"));
+                }
+                if let Some(interval) = field(group, "interval") {
+                    parts.push(format!("interval={interval}"));
+                }
+                if let Some(tolerance) = field(group, "tolerance") {
+                    parts.push(format!("tolerance={tolerance}"));
+                }
+                // milliseconds to seconds
+                if let Some(timeout) = group.get("timeout").and_then(Value::as_u64) {
+                    parts.push(format!("timeout={}", (timeout / 1000).max(1)));
+                }
+            }
+            if flag(group, "hidden") {
+                parts.push("hidden=true".into());
+            }
+            groups.push(format!("{name} = {}", parts.join(", ")));
+        }
+
+        let mut rules = vec![];
+        for rule in self.rules() {
+            match self.surge_rule(rule) {
+                Ok(line) => rules.push(line),
+                Err(err) => self.skip("rule", rule, err.to_string()),
+            }
+        }
+
+        let mut hosts = vec![];
+        if let Some(map) = sub_map(self.config, "hosts") {
+            for (host, value) in map.iter() {
+                let host = host.as_str().unwrap_or_default();
+                let value = match value {
+                    Value::Sequence(s) => s
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    v => v.as_str().unwrap_or_default().into(),
+                };
+                hosts.push(format!("{host} = {value}"));
+            }
+        }
+        if let Some(map) = dns.and_then(|d| sub_map(d, "nameserver-policy")) {
+            for (host, value) in map.iter() {
+                if let (Some(host), Some(server)) = (host.as_str(), value.as_str()) {
+                    hosts.push(format!("{host} = server:{server}"));
+                }
+            }
+        }
+
+        let proxies = self.proxies.iter().map(|(_, line)| line.clone());
+        let mut data = String::new();
+        write_section(&mut data, "General", general);
+        write_section(&mut data, "Proxy", proxies.collect());
+        write_section(&mut data, "Proxy Group", groups);
+        write_section(&mut data, "Rule", rules);
+        write_section(&mut data, "Host", hosts);
+        data
+    }
+
+    fn surge_proxy(&self, proxy: &Mapping) -> Result<String> {
+        let name = field(proxy, "name").unwrap_or_default();
+        let ptype = field(proxy, "type").unwrap_or_default();
+        let server = field(proxy, "server").unwrap_or_default();
+        let port = field(proxy, "port").unwrap_or_default();
+        let tls = flag(proxy, "tls");
+
+        let mut kind = ptype.clone();
+        let mut parts = vec![];
+        let mut push = |key: &str, value: Option<String>| {
+            if let Some(value) = value {
+                parts.push(format!("{key}={value}"));
+            }
+        };
+
+        match ptype.as_str() {
+            "ss" => {
+                push("encrypt-method", field(proxy, "cipher"));
+                push("password", field(proxy, "password"));
+                if let Some(plugin) = field(proxy, "plugin") {
+                    let opts = sub_map(proxy, "plugin-opts");
+                    match plugin.as_str() {
+                        "obfs" => {
+                            push("obfs", opts.and_then(|o| field(o, "mode")));
+                            push("obfs-host", opts.and_then(|o| field(o, "host")));
+                        }
+                        plugin => bail!("plugin `{plugin}` is not supported"),
+                    }
+                }
+            }
+            "vmess" => {
+                push("username", field(proxy, "uuid"));
+                if proxy.get("alterId").and_then(Value::as_u64).unwrap_or(0) == 0 {
+                    push("vmess-aead", Some("true".into()));
+                }
+            }
+            "vless" if self.format == ExportFormat::Shadowrocket => {
+                if proxy.contains_key("reality-opts") {
+                    bail!("reality is not supported");
+                }
+                push("password", field(proxy, "uuid"));
+                push("flow", field(proxy, "flow"));
+            }
+            "trojan" | "hysteria2" => {
+                push("password", field(proxy, "password"));
+            }
+            "http" | "socks5" => {
+                if tls {
+                    kind = format!("{ptype}{}", if ptype == "http" { "s" } else { "-tls" });
+                }
+            }
+            "snell" => {
+                push("psk", field(proxy, "psk"));
+                push("version", field(proxy, "version"));
+                let opts = sub_map(proxy, "obfs-opts");
+                push("obfs", opts.and_then(|o| field(o, "mode")));
+                push("obfs-host", opts.and_then(|o| field(o, "host")));
+            }
+            "tuic" => match field(proxy, "token") {
+                Some(token) => push("token", Some(token)),
+                None => {
+                    kind = "tuic-v5".into();
+                    push("uuid", field(proxy, "uuid"));
+                    push("password", field(proxy, "password"));
+                }
+            },
+            ptype => bail!("proxy type `{ptype}` is not supported by {}", self.format),
+        }
+
+        match field(proxy, "network").as_deref() {
+            None | Some("tcp") => {}
+            Some("ws") => {
+                push("ws", Some("true".into()));
+                push("ws-path", Some(ws_path(proxy)));
+                push("ws-headers", ws_host(proxy).map(|h| format!("Host:{h}")));
+            }
+            Some(network) => bail!("transport `{network}` is not supported"),
+        }
+
+        if tls && matches!(ptype.as_str(), "vmess" | "vless") {
+            push("tls", Some("true".into()));
+        }
+        push("sni", field(proxy, "sni").or(field(proxy, "servername")));
+        if flag(proxy, "skip-cert-verify") {
+            push("skip-cert-verify", Some("true".into()));
+        }
+        if flag(proxy, "udp") {
+            push("udp-relay", Some("true".into()));
+        }
+        if flag(proxy, "tfo") {
+            push("tfo", Some("true".into()));
+        }
+
+        // the positional username and password
+        let mut head = vec![kind, server, port];
+        if matches!(ptype.as_str(), "http" | "socks5") {
+            if let Some(username) = field(proxy, "username") {
+                head.push(username);
+                head.push(field(proxy, "password").unwrap_or_default());
+            }
+        }
+        head.extend(parts);
+        Ok(format!("{name} = {}", head.join(", ")))
+    }
+
+    fn surge_rule(&self, rule: &str) -> Result<String> {
+        let (rtype, payload, policy, no_resolve) = Self::split_rule(rule)?;
+        if !self.available.contains(&policy) {
+            bail!("policy `{policy}` is not available");
+        }
+        let policy = self.policy(&policy);
+
+        let line = match rtype.as_str() {
+            "MATCH" => return Ok(format!("FINAL,{policy}")),
+            "DST-PORT" => format!("DEST-PORT,{payload},{policy}"),
+            "SRC-IP-CIDR" => format!("SRC-IP,{payload},{policy}"),
+            "NETWORK" => format!("PROTOCOL,{},{policy}", payload.to_ascii_uppercase()),
+            "RULE-SET" => {
+                let (url, behavior) = self.rule_provider(&payload)?;
+                match behavior.as_str() {
+                    "domain" => format!("DOMAIN-SET,{url},{policy}"),
+                    _ => format!("RULE-SET,{url},{policy}"),
+                }
+            }
+            rtype if SURGE_RULES.contains(&rtype) || rtype == "NOT" => {
+                format!("{rtype},{payload},{policy}")
+            }
+            rtype => bail!("rule type `{rtype}` is not supported"),
+        };
+
+        Ok(match no_resolve {
+            true => format!("{line},no-resolve"),
+            false => line,
+        })
+    }
+
+    /// ## Quantumult X
+    fn quanx(&mut self) -> String {
+        self.prepare(Self::quanx_proxy);
+
+        let mut dns = vec![];
+        let dns_map = sub_map(self.config, "dns");
+        for server in dns_map.map(|d| names(d, "nameserver")).unwrap_or_default() {
+            match server.strip_prefix("https://") {
+                Some(_) => dns.push(format!("doh-server={server}")),
+                None if !server.contains("://") => dns.push(format!("server={server}")),
+                None => {}
+            }
+        }
+        if let Some(map) = sub_map(self.config, "hosts") {
+            for (host, value) in map.iter() {
+                if let (Some(host), Some(ip)) = (host.as_str(), value.as_str()) {
+                    dns.push(format!("address=/{host}/{ip}"));
+                }
+            }
+        }
+
+        let mut policies = vec![];
+        for (group, members) in std::mem::take(&mut self.groups) {
+            let name = field(group, "name").unwrap_or_default();
+            let gtype = field(group, "type").unwrap_or_default();
+            let kind = match gtype.as_str() {
+                "select" => "static",
+                "url-test" => "url-latency-benchmark",
+                "fallback" => "available",
+                "load-balance" => "round-robin",
+                gtype => {
+                    self.skip(
+                        "proxy-group",
+                        &name,
+                        format!("`{gtype}` is converted to `static`"),
+                    );
+                    "static"
+                }
+            };
+
+            let mut parts = vec![name.clone()];
+            parts.extend(members);
+            if group.contains_key("use") {
+                self.skip("proxy-group", &name, "proxy providers are not supported");
+            }
+            if flag(group, "include-all") || field(group, "filter").is_some() {
+                let filter = field(group, "filter").unwrap_or(".*".into());
+                parts.push(format!("server-tag-regex={filter}"));
+            }
+            if kind == "url-latency-benchmark" {
+                if let Some(interval) = field(group, "interval") {
+                    parts.push(format!("check-interval={interval}"));
+                }
+                if let Some(tolerance) = field(group, "tolerance") {
+                    parts.push(format!("tolerance={tolerance}"));
+                }
+            }
+            if parts.len() == 1 {
+                self.skip("proxy-group", &name, "no available member");
+                continue;
+            }
+            policies.push(format!("{kind}={}", parts.join(", ")));
+        }
+
+        let mut filter_remote = vec![];
+        let mut filter_local = vec![];
+        for rule in self.rules() {
+            match self.quanx_rule(rule) {
+                Ok(line) if line.contains("force-policy=") => filter_remote.push(line),
+                Ok(line) => filter_local.push(line),
+                Err(err) => self.skip("rule", rule, err.to_string()),
+            }
+        }
+
+        let proxies = self.proxies.iter().map(|(_, line)| line.clone());
+        let mut data = String::new();
+        write_section(&mut data, "dns", dns);
+        write_section(&mut data, "policy", policies);
+        write_section(&mut data, "server_local", proxies.collect());
+        write_section(&mut data, "filter_remote", filter_remote);
+        write_section(&mut data, "filter_local", filter_local);
+        data
+    }
+
+    fn quanx_proxy(&self, proxy: &Mapping) -> Result<String> {
+        let name = field(proxy, "name").unwrap_or_default();
+        let ptype = field(proxy, "type").unwrap_or_default();
+        let server = field(proxy, "server").unwrap_or_default();
+        let port = field(proxy, "port").unwrap_or_default();
+        let tls = flag(proxy, "tls");
+        let sni = field(proxy, "sni").or(field(proxy, "servername"));
+
+        let mut parts = vec![format!("{server}:{port}")];
+        let mut push = |key: &str, value: Option<String>| {
+            if let Some(value) = value {
+                parts.push(format!("{key}={value}"));
+            }
+        };
+
+        let kind = match ptype.as_str() {
+            "ss" => {
+                push("method", field(proxy, "cipher"));
+                push("password", field(proxy, "password"));
+                if let Some(plugin) = field(proxy, "plugin") {
+                    let opts = sub_map(proxy, "plugin-opts");
+                    match plugin.as_str() {
+                        "obfs" => {
+                            push("obfs", opts.and_then(|o| field(o, "mode")));
+                            push("obfs-host", opts.and_then(|o| field(o, "host")));
+                        }
+                        plugin => bail!("plugin `{plugin}` is not supported"),
+                    }
+                }
+                "shadowsocks"
+            }
+            "vmess" | "vless" => {
+                if proxy.contains_key("reality-opts") {
+                    bail!("reality is not supported");
+                }
+                let method = match ptype.as_str() {
+                    "vless" => "none".into(),
+                    _ => match field(proxy, "cipher").as_deref() {
+                        None | Some("auto") => "chacha20-ietf-poly1305".into(),
+                        Some(cipher) => cipher.to_string(),
+                    },
+                };
+                push("method", Some(method));
+                push("password", field(proxy, "uuid"));
+                if ptype == "vmess" && proxy.get("alterId").and_then(Value::as_u64).unwrap_or(0) > 0
+                {
+                    push("aead", Some("false".into()));
+                }
+                ptype.as_str()
+            }
+            "trojan" => {
+                push("password", field(proxy, "password"));
+                "trojan"
+            }
+            "http" | "socks5" => {
+                push("username", field(proxy, "username"));
+                push("password", field(proxy, "password"));
+                ptype.as_str()
+            }
+            ptype => bail!("proxy type `{ptype}` is not supported by {}", self.format),
+        };
+
+        // the transport and tls are both described by `obfs`
+        let over_tls = tls || ptype == "trojan";
+        match field(proxy, "network").as_deref() {
+            None | Some("tcp") => {
+                if over_tls && ptype != "ss" {
+                    match ptype.as_str() {
+                        "vmess" | "vless" => push("obfs", Some("over-tls".into())),
+                        _ => push("over-tls", Some("true".into())),
+                    }
+                }
+            }
+            Some("ws") if ptype != "ss" => {
+                push("obfs", Some(if over_tls { "wss" } else { "ws" }.into()));
+                push("obfs-uri", Some(ws_path(proxy)));
+                push("obfs-host", ws_host(proxy));
+            }
+            Some(network) => bail!("transport `{network}` is not supported"),
+        }
+        if over_tls {
+            push("tls-host", sni);
+            if flag(proxy, "skip-cert-verify") {
+                push("tls-verification", Some("false".into()));
+            }
+        }
+        if flag(proxy, "udp") {
+            push("udp-relay", Some("true".into()));
+        }
+        if flag(proxy, "tfo") {
+            push("fast-open", Some("true".into()));
+        }
+        push("tag", Some(name));
+
+        Ok(format!("{kind}={}", parts.join(", ")))
+    }
+
+    fn quanx_rule(&self, rule: &str) -> Result<String> {
+        let (rtype, payload, policy, no_resolve) = Self::split_rule(rule)?;
+        if !self.available.contains(&policy) {
+            bail!("policy `{policy}` is not available");
+        }
+        let policy = self.policy(&policy);
+
+        let rtype = match rtype.as_str() {
+            "MATCH" => return Ok(format!("final, {policy}")),
+            "RULE-SET" => {
+                let (url, _) = self.rule_provider(&payload)?;
+                return Ok(format!(
+                    "{url}, tag={payload}, force-policy={policy}, update-interval=86400, opt-parser=true, enabled=true"
+                ));
+            }
+            "DOMAIN" => "host",
+            "DOMAIN-SUFFIX" => "host-suffix",
+            "DOMAIN-KEYWORD" => "host-keyword",
+            "IP-CIDR" => "ip-cidr",
+            "IP-CIDR6" => "ip6-cidr",
+            "GEOIP" => "geoip",
+            "IP-ASN" => "ip-asn",
+            rtype => bail!("rule type `{rtype}` is not supported"),
+        };
+
+        Ok(match no_resolve {
+            true => format!("{rtype}, {payload}, {policy}, no-resolve"),
+            false => format!("{rtype}, {payload}, {policy}"),
+        })
+    }
+}
+
+fn write_section(data: &mut String, name: &str, lines: Vec<String>) {
+    if lines.is_empty() {
+        return;
+    }
+    data.push_str(&format!("[{name}]\n"));
+    for line in lines {
+        data.push_str(&line);
+        data.push('\n');
+    }
+    data.push('\n');
+}
+
+#[test]
+fn test_export() -> anyhow::Result<()> {
+    let config = r#"
+ipv6: false
+dns:
+  nameserver:
+    - 223.5.5.5
+    - https://dns.alidns.com/dns-query
+proxies:
+  - name: hk
+    type: ss
+    server: hk.example.com
+    port: 8388
+    cipher: aes-128-gcm
+    password: pass
+    udp: true
+  - name: jp
+    type: vmess
+    server: jp.example.com
+    port: 443
+    uuid: b831381d-6324-4d53-ad4f-8cda48b30811
+    alterId: 0
+    cipher: auto
+    tls: true
+    servername: cdn.example.com
+    network: ws
+    ws-opts:
+      path: /ray
+      headers:
+        Host: cdn.example.com
+  - name: us
+    type: vless
+    server: us.example.com
+    port: 443
+    uuid: b831381d-6324-4d53-ad4f-8cda48b30811
+    network: grpc
+  - name: wg
+    type: wireguard
+    server: wg.example.com
+    port: 51820
+proxy-groups:
+  - name: PROXY
+    type: select
+    proxies: [auto, hk, jp, us]
+  - name: auto
+    type: url-test
+    proxies: [hk, jp]
+    url: http://www.gstatic.com/generate_204
+    interval: 300
+    timeout: 5000
+  - name: WireGuard
+    type: select
+    proxies: [wg]
+rule-providers:
+  netflix:
+    type: http
+    behavior: domain
+    format: text
+    url: https://example.com/netflix.txt
+rules:
+  - DOMAIN-SUFFIX,google.com,PROXY
+  - RULE-SET,netflix,auto
+  - IP-CIDR,10.0.0.0/8,DIRECT,no-resolve
+  - GEOSITE,cn,DIRECT
+  - DOMAIN,wg.example.com,WireGuard
+  - MATCH,PROXY
+"#;
+    let config = serde_yaml::from_str::<Mapping>(config)?;
+
+    let surge = export_config(&config, ExportFormat::Surge);
+    assert!(surge.data.starts_with("# Exported for Surge"));
+    assert!(surge
+        .data
+        .contains("# [skipped] proxy `us`: proxy type `vless` is not supported by Surge"));
+    assert!(surge.data.contains("PROXY = select, auto, hk, jp\n"));
+    assert!(!surge.data.contains("\nus = ") && !surge.data.contains("\nWireGuard = "));
+    assert!(surge.data.contains(
+        "hk = ss, hk.example.com, 8388, encrypt-method=aes-128-gcm, password=pass, udp-relay=true"
+    ));
+    assert!(surge.data.contains(
+        "auto = url-test, hk, jp, url=http://www.gstatic.com/generate_204, interval=300, timeout=5"
+    ));
+    assert!(surge
+        .data
+        .contains("DOMAIN-SET,https://example.com/netflix.txt,auto"));
+    assert!(surge.data.contains("FINAL,PROXY"));
+    assert!(surge
+        .data
+        .contains("encrypted-dns-server = https://dns.alidns.com/dns-query"));
+    // us, wg, member us, WireGuard group, GEOSITE rule, WireGuard rule
+    assert_eq!(surge.skipped.len(), 6);
+
+    let shadowrocket = export_config(&config, ExportFormat::Shadowrocket);
+    assert!(shadowrocket
+        .skipped
+        .iter()
+        .any(|s| s.name == "us" && s.reason.contains("grpc")));
+
+    let quanx = export_config(&config, ExportFormat::QuantumultX);
+    assert!(quanx.data.starts_with("# Exported for Quantumult X"));
+    assert!(quanx
+        .data
+        .contains("doh-server=https://dns.alidns.com/dns-query"));
+    assert!(quanx.data.contains("static=PROXY, auto, hk, jp\n"));
+    assert!(quanx
+        .data
+        .contains("url-latency-benchmark=auto, hk, jp, check-interval=300"));
+    assert!(!quanx.data.contains("tag=us") && !quanx.data.contains("tag=wg"));
+    assert_eq!(quanx.skipped.len(), 6);
+    assert!(quanx.data.contains("shadowsocks=hk.example.com:8388, method=aes-128-gcm, password=pass, udp-relay=true, tag=hk"));
+    assert!(quanx.data.contains("vmess=jp.example.com:443, method=chacha20-ietf-poly1305, password=b831381d-6324-4d53-ad4f-8cda48b30811, obfs=wss, obfs-uri=/ray, obfs-host=cdn.example.com, tls-host=cdn.example.com, tag=jp"));
+    assert!(quanx
+        .data
+        .contains("ip-cidr, 10.0.0.0/8, direct, no-resolve"));
+    assert!(quanx.data.contains("force-policy=auto"));
+    assert!(quanx.data.contains("final, PROXY"));
+
+    // the options of the health check are dropped with the converted type
+    let relay = r#"
+proxies:
+  - name: hk
+    type: ss
+    server: hk.example.com
+    port: 8388
+    cipher: aes-128-gcm
+    password: pass
+proxy-groups:
+  - name: chain
+    type: relay
+    proxies: [hk]
+    url: http://www.gstatic.com/generate_204
+    interval: 300
+"#;
+    let relay = serde_yaml::from_str::<Mapping>(relay)?;
+    let surge = export_config(&relay, ExportFormat::Surge);
+    assert!(surge.data.contains("chain = select, hk\n"));
+    assert!(surge
+        .skipped
+        .iter()
+        .any(|s| s.name == "chain" && s.reason.contains("converted to `select`")));
+    Ok(())
+}
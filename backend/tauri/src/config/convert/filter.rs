@@ -0,0 +1,320 @@
+//! Filter and rename the proxies of a profile
+use super::{region_pattern, unique_name, ConvertLogs, REGIONS};
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Sequence, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::OnceLock,
+};
+
+/// rename the proxies whose name matches the `pattern`,
+/// the `replacement` could refer to the captures, e.g. `$1`
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct RenameRule {
+    pub pattern: String,
+    pub replacement: String,
+}
+
+fn compile_region_flags() -> Vec<(&'static str, Regex)> {
+    REGIONS
+        .iter()
+        .filter_map(|(_, flag, pattern)| {
+            let regex = Regex::new(&region_pattern(flag, pattern)).ok()?;
+            Some((*flag, regex))
+        })
+        .collect()
+}
+
+/// the flag emoji of the region detected from the proxy name
+pub fn region_flag(name: &str) -> Option<&'static str> {
+    static FLAGS: OnceLock<Vec<(&'static str, Regex)>> = OnceLock::new();
+    FLAGS
+        .get_or_init(compile_region_flags)
+        .iter()
+        .find(|(_, r)| r.is_match(name))
+        .map(|(flag, _)| *flag)
+}
+
+/// the group fields which bring the members other than `proxies`
+const DYNAMIC_GROUP_KEYS: &[&str] = &[
+    "use",
+    "include-all",
+    "include-all-proxies",
+    "include-all-providers",
+];
+
+/// the compiled filter and rename rules of `PrfOption`
+#[derive(Debug)]
+pub struct ProxyFilter {
+    include: Option<Regex>,
+    exclude: Option<Regex>,
+    rename: Vec<(Regex, String)>,
+    flags: Option<Vec<(&'static str, Regex)>>,
+}
+
+impl ProxyFilter {
+    /// return `None` if there is no rule at all
+    pub fn new(
+        include: Option<&str>,
+        exclude: Option<&str>,
+        rename: &[RenameRule],
+        add_flag: bool,
+    ) -> Result<Option<Self>> {
+        let compile = |pattern: &str| {
+            Regex::new(pattern).with_context(|| format!("invalid proxy filter pattern `{pattern}`"))
+        };
+
+        let include = include.filter(|p| !p.is_empty()).map(compile).transpose()?;
+        let exclude = exclude.filter(|p| !p.is_empty()).map(compile).transpose()?;
+        let rename = rename
+            .iter()
+            .map(|rule| Ok((compile(&rule.pattern)?, rule.replacement.clone())))
+            .collect::<Result<Vec<_>>>()?;
+        let flags = add_flag.then(compile_region_flags);
+
+        if include.is_none() && exclude.is_none() && rename.is_empty() && flags.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            include,
+            exclude,
+            rename,
+            flags,
+        }))
+    }
+
+    fn is_kept(&self, name: &str) -> bool {
+        self.include.as_ref().is_none_or(|r| r.is_match(name))
+            && !self.exclude.as_ref().is_some_and(|r| r.is_match(name))
+    }
+
+    fn rename(&self, name: &str) -> String {
+        let mut name = name.to_string();
+        for (regex, replacement) in self.rename.iter() {
+            name = regex.replace_all(&name, replacement.as_str()).into_owned();
+        }
+
+        // skip the names which already start with a flag
+        let has_flag = name
+            .chars()
+            .next()
+            .is_some_and(|c| ('\u{1F1E6}'..='\u{1F1FF}').contains(&c));
+        if let Some(flags) = self.flags.as_ref().filter(|_| !has_flag) {
+            if let Some((flag, _)) = flags.iter().find(|(_, r)| r.is_match(&name)) {
+                name = format!("{flag} {name}");
+            }
+        }
+        name
+    }
+
+    /// filter and rename the `proxies`, the references in `proxy-groups`
+    /// and `rules` are updated, and the groups left empty are removed
+    pub fn apply(&self, profile: &mut Mapping) -> ConvertLogs {
+        let mut logs = ConvertLogs::new();
+        let Some(Value::Sequence(proxies)) = profile.get_mut("proxies") else {
+            return logs;
+        };
+
+        // old name -> new name, `None` if filtered out
+        let mut renamed: HashMap<String, Option<String>> = HashMap::new();
+        let mut names = HashSet::new();
+        let mut kept = Sequence::new();
+        for mut proxy in std::mem::take(proxies) {
+            let Some(name) = proxy.get("name").and_then(Value::as_str).map(String::from) else {
+                kept.push(proxy);
+                continue;
+            };
+            if !self.is_kept(&name) {
+                renamed.insert(name, None);
+                continue;
+            }
+
+            let new_name = unique_name(&mut names, self.rename(&name));
+            if let Some(proxy) = proxy.as_mapping_mut() {
+                proxy.insert("name".into(), new_name.as_str().into());
+            }
+            renamed.insert(name, Some(new_name));
+            kept.push(proxy);
+        }
+        *proxies = kept;
+
+        let filtered = renamed.values().filter(|n| n.is_none()).count();
+        if filtered > 0 {
+            logs.push((
+                "info".into(),
+                format!("{filtered} proxies are filtered out"),
+            ));
+        }
+
+        let removed = prune_groups(profile, &renamed);
+        for group in removed.iter() {
+            logs.push((
+                "warn".into(),
+                format!("group `{group}` is removed since it has no proxy left"),
+            ));
+        }
+
+        let dropped = update_rules(profile, &renamed, &removed);
+        if dropped > 0 {
+            logs.push((
+                "warn".into(),
+                format!("{dropped} rules are removed since the target is removed"),
+            ));
+        }
+        logs
+    }
+}
+
+/// update the members of the groups, return the names of the removed groups
+fn prune_groups(
+    profile: &mut Mapping,
+    renamed: &HashMap<String, Option<String>>,
+) -> HashSet<String> {
+    let mut removed = HashSet::new();
+    let Some(Value::Sequence(groups)) = profile.get_mut("proxy-groups") else {
+        return removed;
+    };
+
+    for group in groups.iter_mut().filter_map(Value::as_mapping_mut) {
+        if let Some(Value::Sequence(members)) = group.get_mut("proxies") {
+            *members = std::mem::take(members)
+                .into_iter()
+                .filter_map(
+                    |member| match member.as_str().and_then(|m| renamed.get(m)) {
+                        Some(Some(name)) => Some(name.as_str().into()),
+                        Some(None) => None,
+                        None => Some(member),
+                    },
+                )
+                .collect();
+        }
+    }
+
+    // a group may become empty after removing the other empty groups
+    loop {
+        let mut changed = false;
+        for group in groups.iter_mut().filter_map(Value::as_mapping_mut) {
+            let Some(name) = group.get("name").and_then(Value::as_str).map(String::from) else {
+                continue;
+            };
+            if removed.contains(&name) {
+                continue;
+            }
+            if let Some(Value::Sequence(members)) = group.get_mut("proxies") {
+                members.retain(|m| m.as_str().is_none_or(|m| !removed.contains(m)));
+                let is_empty = members.is_empty();
+                let is_dynamic = DYNAMIC_GROUP_KEYS.iter().any(|k| group.contains_key(*k));
+                if is_empty && !is_dynamic {
+                    removed.insert(name);
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    groups.retain(|group| {
+        let name = group.get("name").and_then(Value::as_str);
+        name.is_none_or(|name| !removed.contains(name))
+    });
+    removed
+}
+
+/// rename the targets of the rules, return the count of the removed rules
+fn update_rules(
+    profile: &mut Mapping,
+    renamed: &HashMap<String, Option<String>>,
+    removed: &HashSet<String>,
+) -> usize {
+    let Some(Value::Sequence(rules)) = profile.get_mut("rules") else {
+        return 0;
+    };
+
+    let before = rules.len();
+    *rules = std::mem::take(rules)
+        .into_iter()
+        .filter_map(|rule| {
+            let Some(text) = rule.as_str() else {
+                return Some(rule);
+            };
+            let mut parts = text.split(',').collect::<Vec<_>>();
+            // the target is the last field except the options
+            let mut index = parts.len() - 1;
+            if index > 1 && matches!(parts[index].trim(), "no-resolve" | "src") {
+                index -= 1;
+            }
+
+            let target = parts[index].trim();
+            if removed.contains(target) {
+                return None;
+            }
+            match renamed.get(target) {
+                Some(Some(name)) => {
+                    parts[index] = name;
+                    Some(parts.join(",").into())
+                }
+                Some(None) => None,
+                None => Some(rule),
+            }
+        })
+        .collect();
+    before - rules.len()
+}
+
+#[test]
+fn test_proxy_filter() -> anyhow::Result<()> {
+    let profile = r#"
+proxies:
+  - { name: HK 01, type: ss, server: hk.example.com, port: 8388 }
+  - { name: HK 02 expire, type: ss, server: hk2.example.com, port: 8388 }
+  - { name: Tokyo 01, type: ss, server: jp.example.com, port: 8388 }
+  - { name: Moscow 01, type: ss, server: ru.example.com, port: 8388 }
+proxy-groups:
+  - { name: PROXY, type: select, proxies: [AUTO, RU, HK 01, Tokyo 01] }
+  - { name: AUTO, type: url-test, proxies: [HK 01, HK 02 expire, Tokyo 01] }
+  - { name: RU, type: select, proxies: [Moscow 01] }
+  - { name: Provider, type: select, use: [provider], proxies: [Moscow 01] }
+rules:
+  - DOMAIN-SUFFIX,ru,RU
+  - DOMAIN-SUFFIX,jp,Tokyo 01
+  - IP-CIDR,1.1.1.1/32,PROXY,no-resolve
+  - MATCH,PROXY
+"#;
+    let mut profile = serde_yaml::from_str::<Mapping>(profile)?;
+    let rename = vec![RenameRule {
+        pattern: r"^(\w+) 0?(\d+)$".into(),
+        replacement: "$1-$2".into(),
+    }];
+    let filter = ProxyFilter::new(
+        Some("HK|Tokyo|Moscow"),
+        Some(r"expire|(?i)moscow"),
+        &rename,
+        true,
+    )?;
+    let logs = filter.unwrap().apply(&mut profile);
+    assert_eq!(logs.len(), 3);
+
+    let names = super::proxy_names(profile["proxies"].as_sequence().unwrap());
+    assert_eq!(names, vec!["🇭🇰 HK-1", "🇯🇵 Tokyo-1"]);
+
+    let groups = profile["proxy-groups"].as_sequence().unwrap();
+    assert_eq!(groups.len(), 3);
+    assert_eq!(
+        groups[0]["proxies"],
+        serde_yaml::from_str::<Value>("[AUTO, 🇭🇰 HK-1, 🇯🇵 Tokyo-1]")?
+    );
+    assert_eq!(groups[2]["proxies"].as_sequence().unwrap().len(), 0);
+
+    let rules = profile["rules"].as_sequence().unwrap();
+    assert_eq!(rules.len(), 3);
+    assert_eq!(rules[0], Value::from("DOMAIN-SUFFIX,jp,🇯🇵 Tokyo-1"));
+    assert_eq!(rules[1], Value::from("IP-CIDR,1.1.1.1/32,PROXY,no-resolve"));
+
+    assert!(ProxyFilter::new(None, Some(""), &[], false)?.is_none());
+    assert!(ProxyFilter::new(Some("("), None, &[], false).is_err());
+    Ok(())
+}
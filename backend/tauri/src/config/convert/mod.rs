@@ -0,0 +1,116 @@
+//! Convert the third-party subscription formats into the clash `Mapping`
+mod aggregate;
+mod export;
+mod filter;
+mod share_link;
+mod surge;
+
+pub use self::{aggregate::*, export::*, filter::*, share_link::*, surge::*};
+
+use anyhow::Result;
+use serde_yaml::{Mapping, Sequence, Value};
+use std::collections::HashSet;
+
+/// (level, message) pairs, same as the script logs of `enhance()`
+pub type ConvertLogs = Vec<(String, String)>;
+
+pub const DEFAULT_SELECT_GROUP: &str = "PROXY";
+pub const DEFAULT_AUTO_GROUP: &str = "AUTO";
+pub const DEFAULT_TEST_URL: &str = "http://www.gstatic.com/generate_204";
+
+/// the common regions, `(name, flag emoji, pattern of the proxy names)`,
+/// for the flags of the proxies and the region groups of the aggregate profiles
+pub const REGIONS: &[(&str, &str, &str)] = &[
+    ("HK", "🇭🇰", r"香港|\bhk\b|hong ?kong"),
+    ("TW", "🇹🇼", r"台湾|臺灣|\btw\b|taiwan"),
+    ("JP", "🇯🇵", r"日本|\bjp\b|japan|tokyo|osaka"),
+    ("SG", "🇸🇬", r"新加坡|狮城|\bsg\b|singapore"),
+    (
+        "US",
+        "🇺🇸",
+        r"美国|\bus\b|united states|america|los angeles|san jose",
+    ),
+    ("KR", "🇰🇷", r"韩国|\bkr\b|korea|seoul"),
+    ("UK", "🇬🇧", r"英国|\buk\b|united kingdom|britain|london"),
+    ("DE", "🇩🇪", r"德国|\bde\b|germany|frankfurt"),
+    ("FR", "🇫🇷", r"法国|\bfr\b|france|paris"),
+    ("NL", "🇳🇱", r"荷兰|\bnl\b|netherlands|amsterdam"),
+    ("RU", "🇷🇺", r"俄罗斯|\bru\b|russia|moscow"),
+    ("CA", "🇨🇦", r"加拿大|\bca\b|canada"),
+    ("AU", "🇦🇺", r"澳大利亚|澳洲|\bau\b|australia|sydney"),
+    ("IN", "🇮🇳", r"印度|india|mumbai"),
+];
+
+/// the case-insensitive pattern of the region, which matches the flag emoji as well
+pub fn region_pattern(flag: &str, pattern: &str) -> String {
+    format!("(?i){flag}|{pattern}")
+}
+
+/// build a usable profile from the proxies
+/// with a `select` group, an `url-test` group and a final `MATCH` rule
+pub fn build_profile(proxies: Sequence) -> Mapping {
+    let names = proxy_names(&proxies);
+
+    let mut auto = Mapping::new();
+    auto.insert("name".into(), DEFAULT_AUTO_GROUP.into());
+    auto.insert("type".into(), "url-test".into());
+    auto.insert("url".into(), DEFAULT_TEST_URL.into());
+    auto.insert("interval".into(), 300.into());
+    auto.insert("proxies".into(), names.clone().into());
+
+    let mut select = Mapping::new();
+    select.insert("name".into(), DEFAULT_SELECT_GROUP.into());
+    select.insert("type".into(), "select".into());
+    select.insert(
+        "proxies".into(),
+        std::iter::once(DEFAULT_AUTO_GROUP.to_string())
+            .chain(names)
+            .collect::<Vec<_>>()
+            .into(),
+    );
+
+    let mut profile = Mapping::new();
+    profile.insert("proxies".into(), Value::Sequence(proxies));
+    profile.insert(
+        "proxy-groups".into(),
+        Value::Sequence(vec![select.into(), auto.into()]),
+    );
+    profile.insert(
+        "rules".into(),
+        vec![format!("MATCH,{DEFAULT_SELECT_GROUP}")].into(),
+    );
+    profile
+}
+
+/// collect the `name` of every proxy
+pub fn proxy_names(proxies: &Sequence) -> Vec<String> {
+    proxies
+        .iter()
+        .filter_map(|p| p.get("name").and_then(Value::as_str))
+        .map(str::to_string)
+        .collect()
+}
+
+/// mihomo does not allow the duplicate proxy names
+pub fn unique_name(names: &mut HashSet<String>, name: String) -> String {
+    let mut unique = name.clone();
+    let mut index = 2;
+    while names.contains(&unique) {
+        unique = format!("{name} {index}");
+        index += 1;
+    }
+    names.insert(unique.clone());
+    unique
+}
+
+/// dump the converted profile to yaml
+/// the logs are kept as comments on the top of the file
+pub fn dump_profile(profile: &Mapping, header: &str, logs: &ConvertLogs) -> Result<String> {
+    let mut data = format!("# {header}\n");
+    for (level, msg) in logs.iter() {
+        data.push_str(&format!("# [{level}] {}\n", msg.replace('\n', " ")));
+    }
+    data.push('\n');
+    data.push_str(&serde_yaml::to_string(profile)?);
+    Ok(data)
+}
@@ -0,0 +1,749 @@
+//! Shadowrocket / Surge `.conf` profile import
+use super::{proxy_names, ConvertLogs, DEFAULT_SELECT_GROUP, DEFAULT_TEST_URL};
+use anyhow::{anyhow, bail, Result};
+use serde_yaml::{Mapping, Sequence, Value};
+use std::collections::{HashMap, HashSet};
+
+/// the policies built in the clash core
+const BUILTIN_POLICIES: [&str; 5] = ["DIRECT", "REJECT", "REJECT-DROP", "PASS", "COMPATIBLE"];
+
+/// the rules could be passed to the clash core as it is
+const PLAIN_RULES: [&str; 14] = [
+    "DOMAIN",
+    "DOMAIN-SUFFIX",
+    "DOMAIN-KEYWORD",
+    "DOMAIN-REGEX",
+    "GEOIP",
+    "IP-CIDR",
+    "IP-CIDR6",
+    "IP-ASN",
+    "SRC-IP-CIDR",
+    "SRC-PORT",
+    "DST-PORT",
+    "IN-PORT",
+    "PROCESS-NAME",
+    "PROCESS-PATH",
+];
+
+/// whether the data looks like a `.conf` file of Shadowrocket or Surge
+pub fn is_surge_conf(data: &str) -> bool {
+    data.lines().any(|line| {
+        matches!(
+            line.trim().to_ascii_lowercase().as_str(),
+            "[proxy]" | "[proxy group]" | "[rule]"
+        )
+    })
+}
+
+/// convert the Shadowrocket / Surge `.conf` file to the clash profile,
+/// the lines could not be converted are returned as the logs
+pub fn convert_surge_conf(data: &str) -> Result<(Mapping, ConvertLogs)> {
+    if !is_surge_conf(data) {
+        bail!("the data is not a Shadowrocket or Surge profile");
+    }
+
+    let mut conv = SurgeConverter::default();
+    let mut section = String::new();
+
+    for (index, line) in data.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with(';')
+            || line.starts_with("//")
+        {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            section = line[1..line.len() - 1].trim().to_string();
+            if !conv.is_supported(&section) {
+                conv.warn(format!("section [{section}] is not supported, skipped"));
+            }
+            continue;
+        }
+
+        let res = match section.to_ascii_lowercase().as_str() {
+            "general" => conv.general(line),
+            "proxy" => conv.proxy(line),
+            "proxy group" => conv.proxy_group(line),
+            "rule" => conv.rule(line),
+            "host" => conv.host(line),
+            "url rewrite" => Err(anyhow!("url rewrite is not supported by the clash core")),
+            _ => Ok(()),
+        };
+
+        if let Err(err) = res {
+            conv.warn(format!("[{section}] line {}: {err}", index + 1));
+        }
+    }
+
+    Ok(conv.finish())
+}
+
+#[derive(Debug, Default)]
+struct SurgeConverter {
+    proxies: Sequence,
+    groups: Sequence,
+    rules: Vec<String>,
+    rule_providers: Mapping,
+    proxy_providers: Mapping,
+    hosts: Mapping,
+    dns: Mapping,
+    ipv6: Option<bool>,
+    /// `direct` and `reject` proxies are aliases of the builtin policies
+    alias: HashMap<String, String>,
+    logs: ConvertLogs,
+}
+
+/// the `key = type, a, b, k=v` line
+struct Entry<'a> {
+    name: &'a str,
+    kind: String,
+    args: Vec<&'a str>,
+    opts: HashMap<String, &'a str>,
+}
+
+impl<'a> Entry<'a> {
+    fn parse(line: &'a str) -> Result<Self> {
+        let (name, value) = line.split_once('=').ok_or(anyhow!("missing `=`"))?;
+        let mut parts = value.split(',').map(str::trim);
+        let kind = parts.next().unwrap_or_default().to_ascii_lowercase();
+
+        let mut args = vec![];
+        let mut opts = HashMap::new();
+        for part in parts.filter(|p| !p.is_empty()) {
+            match part.split_once('=') {
+                Some((key, value)) => {
+                    let value = value.trim().trim_matches('"');
+                    opts.insert(key.trim().to_ascii_lowercase(), value);
+                }
+                None => args.push(part.trim_matches('"')),
+            }
+        }
+
+        Ok(Self {
+            name: name.trim(),
+            kind,
+            args,
+            opts,
+        })
+    }
+
+    fn opt(&self, key: &str) -> Option<&'a str> {
+        self.opts.get(key).copied().filter(|v| !v.is_empty())
+    }
+
+    fn flag(&self, key: &str) -> bool {
+        matches!(self.opt(key), Some("true" | "1"))
+    }
+
+    fn arg(&self, index: usize, name: &str) -> Result<&'a str> {
+        self.args
+            .get(index)
+            .copied()
+            .ok_or(anyhow!("missing the {name}"))
+    }
+}
+
+impl SurgeConverter {
+    fn is_supported(&self, section: &str) -> bool {
+        matches!(
+            section.to_ascii_lowercase().as_str(),
+            "general" | "proxy" | "proxy group" | "rule" | "host" | "url rewrite"
+        )
+    }
+
+    fn warn<S: Into<String>>(&mut self, msg: S) {
+        self.logs.push(("warn".into(), msg.into()));
+    }
+
+    fn policy(&self, name: &str) -> String {
+        let name = name.trim();
+        match self.alias.get(name) {
+            Some(alias) => alias.clone(),
+            None => match name.to_ascii_uppercase().as_str() {
+                "REJECT-TINYGIF" | "REJECT-IMG" | "REJECT-DICT" | "REJECT-ARRAY" => "REJECT".into(),
+                upper if BUILTIN_POLICIES.contains(&upper) => upper.into(),
+                _ => name.into(),
+            },
+        }
+    }
+
+    /// ## [General]
+    fn general(&mut self, line: &str) -> Result<()> {
+        let (key, value) = line.split_once('=').ok_or(anyhow!("missing `=`"))?;
+        let key = key.trim().to_ascii_lowercase();
+        let values = value
+            .split(',')
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .map(str::to_string);
+
+        match key.as_str() {
+            "dns-server" | "encrypted-dns-server" => {
+                let servers = values.filter(|v| v != "system").collect::<Vec<_>>();
+                self.append_dns("nameserver", servers);
+            }
+            "fallback-dns-server" => {
+                let servers = values.filter(|v| v != "system").collect::<Vec<_>>();
+                self.append_dns("fallback", servers);
+            }
+            "always-real-ip" => {
+                self.append_dns("fake-ip-filter", values.collect());
+            }
+            "ipv6" => {
+                self.ipv6 = Some(value.trim() == "true");
+            }
+            key => bail!("`{key}` is not supported"),
+        }
+        Ok(())
+    }
+
+    fn append_dns(&mut self, key: &str, values: Vec<String>) {
+        if values.is_empty() {
+            return;
+        }
+        let key = Value::from(key);
+        let mut list = self
+            .dns
+            .get(&key)
+            .and_then(Value::as_sequence)
+            .cloned()
+            .unwrap_or_default();
+        list.extend(values.into_iter().map(Value::from));
+        self.dns.insert("enable".into(), true.into());
+        self.dns.insert(key, list.into());
+    }
+
+    /// ## [Proxy]
+    fn proxy(&mut self, line: &str) -> Result<()> {
+        let entry = Entry::parse(line)?;
+
+        let ptype = match entry.kind.as_str() {
+            "direct" => {
+                self.alias.insert(entry.name.into(), "DIRECT".into());
+                return Ok(());
+            }
+            "reject" | "reject-tinygif" | "reject-drop" => {
+                let policy = match entry.kind.as_str() {
+                    "reject-drop" => "REJECT-DROP",
+                    _ => "REJECT",
+                };
+                self.alias.insert(entry.name.into(), policy.into());
+                return Ok(());
+            }
+            "ss" | "shadowsocks" => "ss",
+            "vmess" => "vmess",
+            "trojan" => "trojan",
+            "http" | "https" => "http",
+            "socks5" | "socks5-tls" => "socks5",
+            "snell" => "snell",
+            "hysteria2" => "hysteria2",
+            "tuic" | "tuic-v5" => "tuic",
+            kind => bail!("proxy type `{kind}` is not supported"),
+        };
+
+        let mut proxy = Mapping::new();
+        proxy.insert("name".into(), entry.name.into());
+        proxy.insert("type".into(), ptype.into());
+        proxy.insert("server".into(), entry.arg(0, "server")?.into());
+        proxy.insert("port".into(), entry.arg(1, "port")?.parse::<u16>()?.into());
+
+        let mut insert = |field: &str, value: Option<&str>| {
+            if let Some(value) = value {
+                proxy.insert(field.into(), value.into());
+            }
+        };
+
+        match entry.kind.as_str() {
+            "ss" | "shadowsocks" => {
+                insert(
+                    "cipher",
+                    entry.opt("encrypt-method").or(entry.opt("method")),
+                );
+                insert("password", entry.opt("password"));
+            }
+            "vmess" => {
+                insert("uuid", entry.opt("username").or(entry.opt("uuid")));
+                insert("cipher", Some(entry.opt("method").unwrap_or("auto")));
+            }
+            "trojan" | "hysteria2" => {
+                insert("password", entry.opt("password"));
+            }
+            "http" | "https" | "socks5" | "socks5-tls" => {
+                insert(
+                    "username",
+                    entry.opt("username").or(entry.args.get(2).copied()),
+                );
+                insert(
+                    "password",
+                    entry.opt("password").or(entry.args.get(3).copied()),
+                );
+            }
+            "snell" => {
+                insert("psk", entry.opt("psk"));
+                insert("version", entry.opt("version"));
+            }
+            "tuic" => {
+                insert("token", entry.opt("token"));
+            }
+            "tuic-v5" => {
+                insert("uuid", entry.opt("uuid"));
+                insert("password", entry.opt("password"));
+            }
+            _ => {}
+        }
+
+        insert("sni", entry.opt("sni"));
+        if let Some(alpn) = entry.opt("alpn") {
+            proxy.insert("alpn".into(), vec![alpn].into());
+        }
+
+        if ptype == "vmess" {
+            if let Some(sni) = proxy.remove("sni") {
+                proxy.insert("servername".into(), sni);
+            }
+            proxy.insert("alterId".into(), 0.into());
+        }
+        if entry.flag("tls") || matches!(entry.kind.as_str(), "https" | "socks5-tls") {
+            proxy.insert("tls".into(), true.into());
+        }
+        if entry.flag("skip-cert-verify") {
+            proxy.insert("skip-cert-verify".into(), true.into());
+        }
+        if entry.flag("udp-relay") || entry.flag("udp") {
+            proxy.insert("udp".into(), true.into());
+        }
+        if entry.flag("tfo") {
+            proxy.insert("tfo".into(), true.into());
+        }
+
+        // simple-obfs for shadowsocks and snell
+        if let Some(obfs) = entry.opt("obfs").filter(|o| *o != "none") {
+            let mut opts = Mapping::new();
+            opts.insert("mode".into(), obfs.into());
+            if let Some(host) = entry.opt("obfs-host") {
+                opts.insert("host".into(), host.into());
+            }
+            match ptype {
+                "ss" => {
+                    proxy.insert("plugin".into(), "obfs".into());
+                    proxy.insert("plugin-opts".into(), opts.into());
+                }
+                "snell" => {
+                    proxy.insert("obfs-opts".into(), opts.into());
+                }
+                _ => self.warn(format!("proxy `{}`: obfs `{obfs}` is ignored", entry.name)),
+            }
+        }
+
+        // websocket transport for vmess and trojan
+        if entry.flag("ws") || entry.opt("obfs") == Some("websocket") {
+            let mut opts = Mapping::new();
+            opts.insert(
+                "path".into(),
+                entry
+                    .opt("ws-path")
+                    .or(entry.opt("path"))
+                    .unwrap_or("/")
+                    .into(),
+            );
+            if let Some(headers) = entry.opt("ws-headers").or(entry.opt("obfs-host")) {
+                let mut map = Mapping::new();
+                for header in headers.split('|') {
+                    match header.split_once(':') {
+                        Some((key, value)) => map.insert(key.trim().into(), value.trim().into()),
+                        None => map.insert("Host".into(), header.trim().into()),
+                    };
+                }
+                opts.insert("headers".into(), map.into());
+            }
+            proxy.insert("network".into(), "ws".into());
+            proxy.insert("ws-opts".into(), opts.into());
+        }
+
+        self.proxies.push(proxy.into());
+        Ok(())
+    }
+
+    /// ## [Proxy Group]
+    fn proxy_group(&mut self, line: &str) -> Result<()> {
+        let entry = Entry::parse(line)?;
+
+        let gtype = match entry.kind.as_str() {
+            "select" | "url-test" | "fallback" | "load-balance" => entry.kind.clone(),
+            "subnet" | "ssid" => {
+                self.warn(format!(
+                    "proxy group `{}`: `{}` is converted to `select`",
+                    entry.name, entry.kind
+                ));
+                "select".into()
+            }
+            kind => bail!("proxy group type `{kind}` is not supported"),
+        };
+
+        let members = entry
+            .args
+            .iter()
+            .map(|name| self.policy(name))
+            .collect::<Vec<_>>();
+
+        let mut group = Mapping::new();
+        group.insert("name".into(), entry.name.into());
+        group.insert("type".into(), gtype.as_str().into());
+        if !members.is_empty() {
+            group.insert("proxies".into(), members.into());
+        }
+
+        // the external policies are loaded by the proxy provider
+        if let Some(url) = entry.opt("policy-path") {
+            let name = format!("{}-policy", entry.name);
+            let mut provider = Mapping::new();
+            provider.insert("type".into(), "http".into());
+            provider.insert("url".into(), url.into());
+            provider.insert("interval".into(), 86400.into());
+            self.proxy_providers
+                .insert(name.as_str().into(), provider.into());
+            group.insert("use".into(), vec![name].into());
+        }
+        if let Some(filter) = entry.opt("policy-regex-filter") {
+            group.insert("filter".into(), filter.into());
+        }
+        if entry.flag("include-all-proxies") {
+            group.insert("include-all-proxies".into(), true.into());
+        }
+        if entry.flag("hidden") {
+            group.insert("hidden".into(), true.into());
+        }
+
+        if gtype != "select" {
+            group.insert(
+                "url".into(),
+                entry.opt("url").unwrap_or(DEFAULT_TEST_URL).into(),
+            );
+            group.insert(
+                "interval".into(),
+                entry
+                    .opt("interval")
+                    .unwrap_or("600")
+                    .parse::<u64>()?
+                    .into(),
+            );
+            if let Some(tolerance) = entry.opt("tolerance") {
+                group.insert("tolerance".into(), tolerance.parse::<u64>()?.into());
+            }
+            // seconds to milliseconds
+            if let Some(timeout) = entry.opt("timeout") {
+                group.insert("timeout".into(), (timeout.parse::<u64>()? * 1000).into());
+            }
+        }
+
+        if !group.contains_key("proxies")
+            && !group.contains_key("use")
+            && !group.contains_key("include-all-proxies")
+        {
+            bail!("proxy group `{}` does not contain any policy", entry.name);
+        }
+
+        self.groups.push(group.into());
+        Ok(())
+    }
+
+    /// ## [Rule]
+    fn rule(&mut self, line: &str) -> Result<()> {
+        let (rtype, rest) = line.split_once(',').unwrap_or((line, ""));
+        let rtype = rtype.trim().to_ascii_uppercase();
+
+        // the logic rules contain commas in the payload
+        let (payload, rest) = match rtype.as_str() {
+            "AND" | "OR" | "NOT" => split_logic_payload(rest)?,
+            "FINAL" | "MATCH" => ("", rest),
+            _ => rest.split_once(',').ok_or(anyhow!("missing the policy"))?,
+        };
+        let mut parts = rest.split(',').map(str::trim);
+        let policy = self.policy(parts.next().unwrap_or_default());
+        if policy.is_empty() {
+            bail!("missing the policy");
+        }
+        let no_resolve = parts.any(|p| p == "no-resolve");
+        let payload = payload.trim();
+
+        let rule = match rtype.as_str() {
+            "FINAL" | "MATCH" => format!("MATCH,{policy}"),
+            "AND" | "OR" | "NOT" => format!("{rtype},{payload},{policy}"),
+            "SRC-IP" => match payload.contains('/') {
+                true => format!("SRC-IP-CIDR,{payload},{policy}"),
+                false => format!("SRC-IP-CIDR,{payload}/32,{policy}"),
+            },
+            "DEST-PORT" => format!("DST-PORT,{payload},{policy}"),
+            "PROTOCOL" => format!("NETWORK,{},{policy}", payload.to_ascii_lowercase()),
+            "RULE-SET" | "DOMAIN-SET" => {
+                if !payload.contains("://") {
+                    bail!("builtin rule set `{payload}` is not supported");
+                }
+                let behavior = match rtype.as_str() {
+                    "DOMAIN-SET" => "domain",
+                    _ => "classical",
+                };
+                let name = self.rule_provider(payload, behavior);
+                format!("RULE-SET,{name},{policy}")
+            }
+            rtype if PLAIN_RULES.contains(&rtype) => format!("{rtype},{payload},{policy}"),
+            rtype => bail!("rule type `{rtype}` is not supported"),
+        };
+
+        match no_resolve {
+            true => self.rules.push(format!("{rule},no-resolve")),
+            false => self.rules.push(rule),
+        }
+        Ok(())
+    }
+
+    /// add the rule provider and return its name
+    fn rule_provider(&mut self, url: &str, behavior: &str) -> String {
+        let stem = url
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .and_then(|f| f.split(['.', '?']).next())
+            .filter(|s| !s.is_empty())
+            .unwrap_or("rule-set")
+            .to_string();
+
+        let mut name = stem.clone();
+        let mut index = 2;
+        while self.rule_providers.contains_key(name.as_str()) {
+            name = format!("{stem}-{index}");
+            index += 1;
+        }
+
+        let mut provider = Mapping::new();
+        provider.insert("type".into(), "http".into());
+        provider.insert("behavior".into(), behavior.into());
+        provider.insert("format".into(), "text".into());
+        provider.insert("url".into(), url.into());
+        provider.insert("interval".into(), 86400.into());
+        self.rule_providers
+            .insert(name.as_str().into(), provider.into());
+        name
+    }
+
+    /// ## [Host]
+    fn host(&mut self, line: &str) -> Result<()> {
+        let (host, value) = line.split_once('=').ok_or(anyhow!("missing `=`"))?;
+        let (host, value) = (host.trim(), value.trim());
+
+        match value.strip_prefix("server:") {
+            Some(server) => {
+                let key = Value::from("nameserver-policy");
+                let mut policy = self
+                    .dns
+                    .get(&key)
+                    .and_then(Value::as_mapping)
+                    .cloned()
+                    .unwrap_or_default();
+                policy.insert(host.into(), server.trim().into());
+                self.dns.insert(key, policy.into());
+            }
+            None => {
+                let values = value.split(',').map(str::trim).collect::<Vec<_>>();
+                match values.len() {
+                    1 => self.hosts.insert(host.into(), values[0].into()),
+                    _ => self.hosts.insert(host.into(), values.into()),
+                };
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> (Mapping, ConvertLogs) {
+        // Shadowrocket treats `PROXY` as the default selected proxy
+        let referenced = self
+            .rules
+            .iter()
+            .any(|r| r.rsplit(',').any(|p| p == DEFAULT_SELECT_GROUP));
+        let defined = self
+            .groups
+            .iter()
+            .any(|g| g.get("name").and_then(Value::as_str) == Some(DEFAULT_SELECT_GROUP));
+        if referenced && !defined {
+            let mut group = Mapping::new();
+            group.insert("name".into(), DEFAULT_SELECT_GROUP.into());
+            group.insert("type".into(), "select".into());
+            let mut members = self
+                .groups
+                .iter()
+                .filter_map(|g| g.get("name").and_then(Value::as_str))
+                .map(str::to_string)
+                .collect::<Vec<_>>();
+            members.extend(proxy_names(&self.proxies));
+            if members.is_empty() {
+                members.push("DIRECT".into());
+            }
+            group.insert("proxies".into(), members.into());
+            self.groups.insert(0, group.into());
+        }
+
+        self.check_policies();
+
+        let mut profile = Mapping::new();
+        if let Some(ipv6) = self.ipv6 {
+            profile.insert("ipv6".into(), ipv6.into());
+        }
+        if !self.dns.is_empty() {
+            profile.insert("dns".into(), self.dns.into());
+        }
+        if !self.hosts.is_empty() {
+            profile.insert("hosts".into(), self.hosts.into());
+        }
+        profile.insert("proxies".into(), self.proxies.into());
+        if !self.proxy_providers.is_empty() {
+            profile.insert("proxy-providers".into(), self.proxy_providers.into());
+        }
+        profile.insert("proxy-groups".into(), self.groups.into());
+        if !self.rule_providers.is_empty() {
+            profile.insert("rule-providers".into(), self.rule_providers.into());
+        }
+        profile.insert("rules".into(), self.rules.into());
+
+        (profile, self.logs)
+    }
+
+    /// report the policies referenced but not defined
+    fn check_policies(&mut self) {
+        let mut defined: HashSet<String> = BUILTIN_POLICIES.iter().map(|s| s.to_string()).collect();
+        defined.extend(proxy_names(&self.proxies));
+        defined.extend(proxy_names(&self.groups));
+
+        let mut missing = vec![];
+        for group in self.groups.iter() {
+            let members = group.get("proxies").and_then(Value::as_sequence);
+            for member in members.into_iter().flatten().filter_map(Value::as_str) {
+                if !defined.contains(member) {
+                    missing.push(member.to_string());
+                }
+            }
+        }
+        for rule in self.rules.iter() {
+            let policy = rule.trim_end_matches(",no-resolve").rsplit(',').next();
+            if let Some(policy) = policy.filter(|p| !defined.contains(*p)) {
+                missing.push(policy.to_string());
+            }
+        }
+
+        missing.sort();
+        missing.dedup();
+        for policy in missing {
+            self.warn(format!("policy `{policy}` is not defined"));
+        }
+    }
+}
+
+/// split `((DOMAIN,a),(DST-PORT,443)),POLICY` into the payload and the rest
+fn split_logic_payload(rest: &str) -> Result<(&str, &str)> {
+    let rest = rest.trim_start();
+    let mut depth = 0;
+    for (i, c) in rest.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    let (payload, rest) = rest.split_at(i + 1);
+                    return Ok((payload, rest.trim_start_matches(',')));
+                }
+            }
+            _ => {}
+        }
+    }
+    bail!("unbalanced parentheses in the logic rule")
+}
+
+#[test]
+fn test_surge_conf() -> anyhow::Result<()> {
+    let conf = r#"
+[General]
+dns-server = system, 223.5.5.5, 119.29.29.29
+skip-proxy = 192.168.0.0/16
+ipv6 = false
+
+[Proxy]
+HK = ss, hk.example.com, 8388, encrypt-method=aes-128-gcm, password=pass, obfs=http, obfs-host=bing.com, udp-relay=true
+JP = vmess, jp.example.com, 443, username=b831381d-6324-4d53-ad4f-8cda48b30811, ws=true, ws-path=/ray, ws-headers=Host:cdn.example.com, tls=true, sni=cdn.example.com
+Block = reject
+WG = wireguard, section-name=home
+
+[Proxy Group]
+Auto = url-test, HK, JP, url=http://www.gstatic.com/generate_204, interval=600, timeout=5
+Streaming = select, Auto, HK, DIRECT, Block
+
+[Rule]
+DOMAIN-SUFFIX,google.com,Auto
+IP-CIDR,10.0.0.0/8,DIRECT,no-resolve
+RULE-SET,https://example.com/rules/Netflix.list,Streaming
+AND,((DOMAIN-SUFFIX,example.com),(DST-PORT,443)),Block
+USER-AGENT,Instagram*,DIRECT
+FINAL,PROXY
+
+[Host]
+router.lan = 192.168.1.1
+*.corp.com = server:10.0.0.1
+
+[URL Rewrite]
+^https?://(www.)?g.cn https://www.google.com 302
+
+[MITM]
+hostname = *.google.com
+"#;
+
+    let (profile, logs) = convert_surge_conf(conf)?;
+
+    assert_eq!(profile["ipv6"], Value::from(false));
+    assert_eq!(profile["dns"]["enable"], Value::from(true));
+    assert_eq!(
+        profile["dns"]["nameserver"],
+        serde_yaml::from_str::<Value>("[223.5.5.5, 119.29.29.29]")?
+    );
+
+    let proxies = profile["proxies"].as_sequence().unwrap();
+    assert_eq!(proxies.len(), 2);
+    assert_eq!(proxies[0]["plugin"], Value::from("obfs"));
+    assert_eq!(proxies[1]["network"], Value::from("ws"));
+
+    let groups = profile["proxy-groups"].as_sequence().unwrap();
+    assert_eq!(groups.len(), 3);
+    assert_eq!(groups[0]["name"], Value::from("PROXY"));
+    assert_eq!(groups[1]["timeout"], Value::from(5000));
+    assert_eq!(groups[2]["proxies"][3], Value::from("REJECT"));
+
+    let rules = profile["rules"].as_sequence().unwrap();
+    assert_eq!(rules.len(), 5);
+    assert_eq!(
+        rules[1],
+        Value::from("IP-CIDR,10.0.0.0/8,DIRECT,no-resolve")
+    );
+    assert_eq!(rules[2], Value::from("RULE-SET,Netflix,Streaming"));
+    assert_eq!(
+        rules[3],
+        Value::from("AND,((DOMAIN-SUFFIX,example.com),(DST-PORT,443)),REJECT")
+    );
+    assert_eq!(rules[4], Value::from("MATCH,PROXY"));
+
+    assert!(profile["rule-providers"]["Netflix"].is_mapping());
+    assert_eq!(profile["hosts"]["router.lan"], Value::from("192.168.1.1"));
+    assert_eq!(
+        profile["dns"]["nameserver-policy"]["*.corp.com"],
+        Value::from("10.0.0.1")
+    );
+
+    // skip-proxy, wireguard, user-agent, url rewrite, mitm
+    assert_eq!(logs.len(), 5);
+    assert!(logs.iter().all(|(level, _)| level == "warn"), "{logs:?}");
+    assert!(
+        logs.iter()
+            .any(|(_, msg)| msg.contains("proxy type `wireguard` is not supported")),
+        "{logs:?}"
+    );
+    Ok(())
+}
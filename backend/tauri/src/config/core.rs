@@ -84,12 +84,13 @@ impl Config {
 
     /// 生成配置存好
     pub fn generate() -> Result<()> {
-        let (config, exists_keys, logs) = enhance::enhance();
+        let (config, exists_keys, logs, errors) = enhance::enhance();
 
         *Config::runtime().draft() = IRuntime {
             config: Some(config),
             exists_keys,
             chain_logs: logs,
+            validation: errors,
         };
 
         Ok(())
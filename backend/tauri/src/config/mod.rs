@@ -1,4 +1,6 @@
+mod changes;
 mod clash;
+pub mod convert;
 mod core;
 mod draft;
 pub mod shadowrocket;
@@ -6,6 +8,6 @@ mod prfitem;
 mod profiles;
 mod runtime;
 
-pub use self::{clash::*, core::*, draft::*, prfitem::*, profiles::*, runtime::*};
+pub use self::{changes::*, clash::*, core::*, draft::*, prfitem::*, profiles::*, runtime::*};
 
 pub use self::shadowrocket::IVerge;
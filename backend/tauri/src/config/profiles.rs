@@ -1,19 +1,41 @@
-use super::prfitem::PrfItem;
-use crate::utils::{dirs, help};
+use super::{
+    convert::{self, ConvertLogs, RegionGroup},
+    prfitem::{PrfAggregate, PrfItem},
+};
+use crate::utils::{crypto, dirs, help};
 use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
 use serde_yaml::Mapping;
-use std::{fs, io::Write};
+use std::{collections::HashSet, fs, io::Write};
+
+/// the version of the `profiles.yaml` schema
+/// 1: the items own their chains
+const PROFILES_VERSION: u32 = 1;
+
+/// whether the global chain runs before or after the chain of the current profile
+#[derive(Default, Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChainOrder {
+    Before,
+    #[default]
+    After,
+}
 
 /// Define the `profiles.yaml` schema
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
 pub struct IProfiles {
+    /// the version of the schema, for the migration
+    pub version: Option<u32>,
+
     /// same as PrfConfig.current
     pub current: Option<String>,
 
     /// same as PrfConfig.chain
+    /// the global chain applied to every profile
     pub chain: Option<Vec<String>>,
 
+    pub chain_order: Option<ChainOrder>,
+
     /// record valid fields for clash
     pub valid: Option<Vec<String>>,
 
@@ -44,6 +66,8 @@ impl IProfiles {
                         }
                     }
                 }
+                profiles.decrypt_secrets();
+                profiles.migrate();
                 profiles
             }
             Err(err) => {
@@ -55,6 +79,7 @@ impl IProfiles {
 
     pub fn template() -> Self {
         Self {
+            version: Some(PROFILES_VERSION),
             valid: Some(vec![
                 "dns".into(),
                 "unified-delay".into(),
@@ -66,14 +91,89 @@ impl IProfiles {
     }
 
     pub fn save_file(&self) -> Result<()> {
-        help::save_yaml(
-            &dirs::profiles_path()?,
-            self,
-            Some("# Profiles Config for Shadowrocket"),
-        )
+        let path = dirs::profiles_path()?;
+        let prefix = Some("# Profiles Config for Shadowrocket");
+        if !crypto::is_enabled() {
+            return help::save_yaml(&path, self, prefix);
+        }
+
+        // only the file is encrypted, the secrets are kept plain in memory
+        let mut profiles = self.clone();
+        profiles.encrypt_secrets()?;
+        help::save_yaml(&path, &profiles, prefix)
     }
 
-    /// 只修改current，valid和chain
+    /// encrypt the secrets if the encryption is enabled
+    pub fn encrypt_secrets(&mut self) -> Result<()> {
+        for item in self.items.iter_mut().flatten() {
+            for secret in item.secrets_mut() {
+                *secret = crypto::encrypt(secret)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// decrypt the secrets, the ones failed to decrypt are kept as they are
+    pub fn decrypt_secrets(&mut self) {
+        for item in self.items.iter_mut().flatten() {
+            for secret in item.secrets_mut() {
+                match crypto::decrypt(secret) {
+                    Ok(plain) => *secret = plain,
+                    Err(err) => {
+                        log::error!(target: "app", "failed to decrypt the secret of the profile: {err}")
+                    }
+                }
+            }
+        }
+    }
+
+    /// migrate the old schema, it's saved when the app starts
+    fn migrate(&mut self) {
+        let version = self.version.unwrap_or(0);
+        if version >= PROFILES_VERSION {
+            return;
+        }
+
+        // 0 -> 1: the global chain is kept as is and runs after the chain of the profile,
+        // the uids of the deleted or non-enhanced items are dropped
+        if version < 1 {
+            let enhanced = self
+                .items
+                .iter()
+                .flatten()
+                .filter(|i| matches!(i.itype.as_deref(), Some("merge" | "script")))
+                .filter_map(|i| i.uid.clone())
+                .collect::<HashSet<_>>();
+            if let Some(chain) = self.chain.as_mut() {
+                chain.retain(|uid| enhanced.contains(uid));
+            }
+            self.chain_order = Some(ChainOrder::After);
+        }
+
+        log::info!(target: "app", "migrate the profiles from version {version} to {PROFILES_VERSION}");
+        self.version = Some(PROFILES_VERSION);
+    }
+
+    /// the merge / script uids applied to the current profile in order
+    pub fn active_chain(&self) -> Vec<String> {
+        let global = self.chain.clone().unwrap_or_default();
+        let own = self
+            .current
+            .as_ref()
+            .and_then(|uid| self.get_item(uid).ok())
+            .and_then(|item| item.chain.clone())
+            .unwrap_or_default();
+
+        let ordered = match self.chain_order.unwrap_or_default() {
+            ChainOrder::Before => global.into_iter().chain(own),
+            ChainOrder::After => own.into_iter().chain(global),
+        };
+        // the item in both chains only runs once
+        let mut seen = HashSet::new();
+        ordered.filter(|uid| seen.insert(uid.clone())).collect()
+    }
+
+    /// 只修改current，valid，chain和chain_order
     pub fn patch_config(&mut self, patch: IProfiles) -> Result<()> {
         if self.items.is_none() {
             self.items = Some(vec![]);
@@ -92,6 +192,10 @@ impl IProfiles {
             self.chain = Some(chain);
         }
 
+        if let Some(chain_order) = patch.chain_order {
+            self.chain_order = Some(chain_order);
+        }
+
         if let Some(valid) = patch.valid {
             self.valid = Some(valid);
         }
@@ -187,6 +291,21 @@ impl IProfiles {
 
         for each in items.iter_mut() {
             if each.uid == Some(uid.clone()) {
+                // refetch the whole profile to apply the new rules of the proxies,
+                // or from the new url or with the new request options
+                let mut reset_validators = item.url.is_some() && item.url != each.url;
+                let mut reset_transport = false;
+                if let Some(option) = item.option.as_ref() {
+                    let old_option = each.option.clone().unwrap_or_default();
+                    reset_validators |= option.is_filter_changed(&old_option)
+                        || option.is_request_changed(&old_option);
+                    reset_transport = option.is_transport_changed(&old_option);
+                }
+                if reset_validators {
+                    each.etag = None;
+                    each.last_modified = None;
+                }
+
                 patch!(each, item, itype);
                 patch!(each, item, name);
                 patch!(each, item, desc);
@@ -196,6 +315,14 @@ impl IProfiles {
                 patch!(each, item, extra);
                 patch!(each, item, updated);
                 patch!(each, item, option);
+                patch!(each, item, aggregate);
+                patch!(each, item, chain);
+                patch!(each, item, transport);
+
+                // try the transport chosen by the user first
+                if reset_transport {
+                    each.transport = None;
+                }
 
                 self.items = Some(items);
                 return self.save_file();
@@ -207,7 +334,7 @@ impl IProfiles {
     }
 
     /// be used to update the remote item
-    /// only patch `updated` `extra` `file_data`
+    /// only patch `updated` `extra` `etag` `last_modified` `transport` `file_data`
     pub fn update_item(&mut self, uid: String, mut item: PrfItem) -> Result<()> {
         if self.items.is_none() {
             self.items = Some(vec![]);
@@ -223,6 +350,11 @@ impl IProfiles {
                 if each.uid == some_uid {
                     each.extra = item.extra;
                     each.updated = item.updated;
+                    each.etag = item.etag.take();
+                    each.last_modified = item.last_modified.take();
+                    if item.transport.is_some() {
+                        each.transport = item.transport;
+                    }
 
                     // save the file data
                     // move the field value after save
@@ -278,6 +410,17 @@ impl IProfiles {
             }
         }
 
+        // remove it from the chains
+        let is_other = |c: &String| *c != uid;
+        if let Some(chain) = self.chain.as_mut() {
+            chain.retain(is_other);
+        }
+        for item in items.iter_mut() {
+            if let Some(chain) = item.chain.as_mut() {
+                chain.retain(is_other);
+            }
+        }
+
         // delete the original uid
         if current == uid {
             self.current = match !items.is_empty() {
@@ -291,6 +434,71 @@ impl IProfiles {
         Ok(current == uid)
     }
 
+    /// generate the `aggregate` profile data from the sources
+    pub fn generate_aggregate(&self, aggregate: &PrfAggregate) -> Result<(String, ConvertLogs)> {
+        let mut sources = vec![];
+        let mut logs = ConvertLogs::new();
+
+        for uid in aggregate.sources.iter() {
+            let Ok(item) = self.get_item(uid) else {
+                logs.push(("warn".into(), format!("the source `{uid}` does not exist")));
+                continue;
+            };
+            let name = item.name.clone().unwrap_or(uid.clone());
+            match item.itype.as_deref() {
+                Some("remote" | "local") => {}
+                _ => {
+                    logs.push((
+                        "warn".into(),
+                        format!("`{name}` is not a remote or local profile"),
+                    ));
+                    continue;
+                }
+            }
+            let profile = item
+                .read_file()
+                .and_then(|data| Ok(serde_yaml::from_str::<Mapping>(&data)?));
+            match profile {
+                Ok(profile) => sources.push((name, profile)),
+                Err(err) => logs.push(("warn".into(), format!("failed to read `{name}`: {err}"))),
+            }
+        }
+
+        let (proxies, proxies_logs) = convert::aggregate_proxies(
+            sources,
+            aggregate.prefix_name.unwrap_or(false),
+            aggregate.dedupe.unwrap_or(true),
+        );
+        logs.extend(proxies_logs);
+        if proxies.is_empty() {
+            bail!("the sources do not contain any proxy");
+        }
+
+        let regions = aggregate
+            .region_groups
+            .clone()
+            .unwrap_or_else(RegionGroup::defaults);
+        let (profile, groups_logs) = convert::build_aggregate_profile(proxies, &regions);
+        logs.extend(groups_logs);
+
+        let data = convert::dump_profile(&profile, "Aggregated profile", &logs)?;
+        Ok((data, logs))
+    }
+
+    /// the uid of the `aggregate` profiles using the source
+    pub fn get_aggregates_of(&self, source: &str) -> Vec<String> {
+        self.items
+            .iter()
+            .flatten()
+            .filter(|item| {
+                item.aggregate
+                    .as_ref()
+                    .map_or(false, |a| a.sources.iter().any(|s| s == source))
+            })
+            .filter_map(|item| item.uid.clone())
+            .collect()
+    }
+
     /// 获取current指向的配置内容
     pub fn current_mapping(&self) -> Result<Mapping> {
         match (self.current.as_ref(), self.items.as_ref()) {
@@ -308,3 +516,28 @@ impl IProfiles {
         }
     }
 }
+
+#[test]
+fn test_active_chain() {
+    let item = |uid: &str, chain: Option<Vec<&str>>| PrfItem {
+        uid: Some(uid.into()),
+        chain: chain.map(|c| c.into_iter().map(String::from).collect()),
+        ..PrfItem::default()
+    };
+    let mut profiles = IProfiles {
+        current: Some("a".into()),
+        chain: Some(vec!["global".into(), "shared".into()]),
+        items: Some(vec![
+            item("a", Some(vec!["own", "shared"])),
+            item("b", None),
+        ]),
+        ..IProfiles::default()
+    };
+    assert_eq!(profiles.active_chain(), ["own", "shared", "global"]);
+
+    profiles.chain_order = Some(ChainOrder::Before);
+    assert_eq!(profiles.active_chain(), ["global", "shared", "own"]);
+
+    profiles.current = Some("b".into());
+    assert_eq!(profiles.active_chain(), ["global", "shared"]);
+}
@@ -1,3 +1,4 @@
+use crate::enhance::ValidationError;
 use serde::{Deserialize, Serialize};
 use serde_yaml::Mapping;
 use std::collections::HashMap;
@@ -9,6 +10,8 @@ pub struct IRuntime {
     // 这些keys不一定都生效
     pub exists_keys: Vec<String>,
     pub chain_logs: HashMap<String, Vec<(String, String)>>,
+    // 最终配置的检查结果
+    pub validation: Vec<ValidationError>,
 }
 
 impl IRuntime {
@@ -5,8 +5,14 @@ use serde::{Deserialize, Serialize};
 
 mod clash_strategy;
 pub mod logging;
-
-pub use self::clash_strategy::{ClashStrategy, ExternalControllerPortStrategy};
+mod profile_switch;
+mod scheduled_action;
+
+pub use self::{
+    clash_strategy::{ClashStrategy, ExternalControllerPortStrategy},
+    profile_switch::ProfileSwitchRule,
+    scheduled_action::{ScheduledAction, ScheduledActionKind},
+};
 pub use logging::LoggingLevel;
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
@@ -86,6 +92,9 @@ pub struct IVerge {
     /// not show the window on launch
     pub enable_silent_start: Option<bool>,
 
+    /// encrypt the subscription urls and the controller secret in the files
+    pub enable_encrypt_secrets: Option<bool>,
+
     /// set system proxy
     pub enable_system_proxy: Option<bool>,
 
@@ -155,6 +164,49 @@ pub struct IVerge {
 
     /// 是否启用代理托盘选择
     pub clash_tray_selector: Option<bool>,
+
+    /// the max number of the revisions kept for each profile, 0 to disable
+    pub max_profile_revisions: Option<usize>,
+
+    /// alert when the used traffic of a subscription reaches the percent, 0 to disable
+    pub traffic_alert_percent: Option<u8>,
+
+    /// alert when a subscription expires within the days, 0 to disable
+    pub expire_alert_days: Option<u64>,
+
+    /// the WebDAV dir to sync the profiles and settings
+    pub webdav_url: Option<String>,
+
+    pub webdav_username: Option<String>,
+
+    pub webdav_password: Option<String>,
+
+    /// encrypt the secrets of the synced bundle, the same on all the devices
+    pub webdav_sync_passphrase: Option<String>,
+
+    /// the minutes between the syncs, 0 to disable
+    pub webdav_sync_interval: Option<u64>,
+
+    /// the fields of the settings which are kept on this device
+    pub webdav_sync_exclude: Option<Vec<String>>,
+
+    /// switch the profile by the network environment
+    pub enable_profile_switch: Option<bool>,
+
+    /// the first matched rule is applied
+    pub profile_switch_rules: Option<Vec<ProfileSwitchRule>>,
+
+    /// the min seconds between two switches
+    pub profile_switch_cooldown: Option<u64>,
+
+    /// the actions run by the cron expressions
+    pub scheduled_actions: Option<Vec<ScheduledAction>>,
+
+    /// the max milliseconds of a script run, 0 to disable
+    pub script_timeout: Option<u64>,
+
+    /// the max memory in MB of a script run, 0 to disable
+    pub script_memory_limit: Option<u64>,
 }
 
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
@@ -223,6 +275,11 @@ impl IVerge {
             max_log_files: Some(7), // 7 days
             disable_auto_check_update: Some(true),
             clash_tray_selector: Some(true),
+            max_profile_revisions: Some(10),
+            traffic_alert_percent: Some(90),
+            expire_alert_days: Some(3),
+            enable_profile_switch: Some(false),
+            profile_switch_cooldown: Some(60),
             ..Self::default()
         }
     }
@@ -256,6 +313,7 @@ impl IVerge {
         patch!(enable_service_mode);
         patch!(enable_auto_launch);
         patch!(enable_silent_start);
+        patch!(enable_encrypt_secrets);
         patch!(enable_system_proxy);
         patch!(enable_random_port);
         patch!(verge_mixed_port);
@@ -278,5 +336,20 @@ impl IVerge {
         patch!(window_size_state);
         patch!(clash_strategy);
         patch!(clash_tray_selector);
+        patch!(max_profile_revisions);
+        patch!(traffic_alert_percent);
+        patch!(expire_alert_days);
+        patch!(webdav_url);
+        patch!(webdav_username);
+        patch!(webdav_password);
+        patch!(webdav_sync_passphrase);
+        patch!(webdav_sync_interval);
+        patch!(webdav_sync_exclude);
+        patch!(enable_profile_switch);
+        patch!(profile_switch_rules);
+        patch!(profile_switch_cooldown);
+        patch!(scheduled_actions);
+        patch!(script_timeout);
+        patch!(script_memory_limit);
     }
 }
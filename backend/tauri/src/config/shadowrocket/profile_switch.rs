@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+/// switch the profile and the clash mode when the network matches,
+/// all the given conditions should be matched
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ProfileSwitchRule {
+    pub name: Option<String>,
+
+    /// the ip of the default gateway, e.g. `192.168.1.1`
+    pub gateway: Option<String>,
+
+    /// the name of the default interface, e.g. `wlan0`
+    pub interface: Option<String>,
+
+    /// the cidr which contains a local ip, e.g. `10.0.0.0/8`
+    pub cidr: Option<String>,
+
+    /// the uid of the profile to switch to
+    pub profile: Option<String>,
+
+    /// the clash mode to switch to
+    pub mode: Option<String>,
+}
+
+impl ProfileSwitchRule {
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or("unnamed")
+    }
+}
@@ -0,0 +1,220 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// the name, the min and the max of the fields of the crontab with the seconds
+const CRON_FIELDS: [(&str, u32, u32); 6] = [
+    ("second", 0, 59),
+    ("minute", 0, 59),
+    ("hour", 0, 23),
+    ("day", 1, 31),
+    ("month", 1, 12),
+    // 0 and 7 are both sunday
+    ("weekday", 0, 7),
+];
+
+const MONTH_NAMES: [&str; 12] = [
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+
+const WEEKDAY_NAMES: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+const MODES: [&str; 4] = ["rule", "global", "direct", "script"];
+
+/// the action run by the cron expression, e.g. switch to direct mode at 18:00
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct ScheduledAction {
+    /// unique, used to identify the task
+    pub id: String,
+
+    pub name: Option<String>,
+
+    /// `sec min hour day month weekday` as the crontab, the seconds could be omitted,
+    /// e.g. `0 18 * * 1-5` runs at 18:00 from monday to friday
+    pub cron: String,
+
+    pub action: ScheduledActionKind,
+
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ScheduledActionKind {
+    /// rule / global / direct / script
+    ChangeMode {
+        mode: String,
+    },
+    EnableSystemProxy,
+    DisableSystemProxy,
+    /// update all the remote profiles
+    UpdateProfiles,
+    RestartCore,
+}
+
+impl ScheduledAction {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.unwrap_or(true)
+    }
+
+    /// check the cron expression and the mode before saving
+    pub fn validate(&self) -> Result<()> {
+        self.cron_with_seconds()?;
+        if let ScheduledActionKind::ChangeMode { mode } = &self.action {
+            if !MODES.contains(&mode.as_str()) {
+                bail!(
+                    "invalid mode `{mode}` of the scheduled action `{}`",
+                    self.id
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// the cron expression with the seconds, which is required by the timer,
+    /// the timer numbers the weekdays from 1 (sunday) to 7 (saturday) unlike the crontab
+    pub fn cron_with_seconds(&self) -> Result<String> {
+        let mut fields = self.cron.split_whitespace().collect::<Vec<_>>();
+        if fields.len() == 5 {
+            fields.insert(0, "0");
+        }
+        if fields.len() != CRON_FIELDS.len() {
+            bail!(
+                "the cron expression `{}` should have 5 or 6 fields",
+                self.cron
+            );
+        }
+
+        let mut translated = vec![];
+        for (field, (name, min, max)) in fields.into_iter().zip(CRON_FIELDS) {
+            let values = parse_cron_field(field, name, min, max).with_context(|| {
+                format!("invalid {name} of the cron expression `{}`", self.cron)
+            })?;
+            match values {
+                Some(values) if name == "weekday" => {
+                    let weekdays = values.into_iter().map(|v| v % 7 + 1);
+                    let weekdays = weekdays.collect::<BTreeSet<_>>();
+                    let weekdays = weekdays.iter().map(|v| v.to_string());
+                    translated.push(weekdays.collect::<Vec<_>>().join(","));
+                }
+                _ => translated.push(field.to_string()),
+            }
+        }
+        Ok(translated.join(" "))
+    }
+}
+
+/// the values of the field, `None` if it matches any value
+fn parse_cron_field(field: &str, name: &str, min: u32, max: u32) -> Result<Option<Vec<u32>>> {
+    if field == "*" || field == "?" {
+        return Ok(None);
+    }
+
+    let parse_value = |value: &str| -> Result<u32> {
+        let names = match name {
+            "month" => &MONTH_NAMES[..],
+            "weekday" => &WEEKDAY_NAMES[..],
+            _ => &[],
+        };
+        let offset = if name == "month" { 1 } else { 0 };
+        let value = match names.iter().position(|n| n.eq_ignore_ascii_case(value)) {
+            Some(index) => index as u32 + offset,
+            None => value
+                .parse()
+                .with_context(|| format!("`{value}` is not a number"))?,
+        };
+        if value < min || value > max {
+            bail!("`{value}` is out of the range {min}-{max}");
+        }
+        Ok(value)
+    };
+
+    let mut values = vec![];
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse().ok().filter(|s| *s > 0)),
+            None => (item, Some(1)),
+        };
+        let Some(step) = step else {
+            bail!("invalid step of `{item}`");
+        };
+        let (start, end) = match range {
+            "*" | "?" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (parse_value(start)?, parse_value(end)?),
+                // `5/15` means from 5 to the max
+                None if item.contains('/') => (parse_value(range)?, max),
+                None => {
+                    let value = parse_value(range)?;
+                    (value, value)
+                }
+            },
+        };
+        if start > end {
+            bail!("invalid range of `{item}`");
+        }
+        values.extend((start..=end).step_by(step));
+    }
+    Ok(Some(values))
+}
+
+#[test]
+fn test_scheduled_action() {
+    let actions: Vec<ScheduledAction> = serde_yaml::from_str(
+        r#"
+- id: direct-at-night
+  cron: "0 18 * * *"
+  action:
+    type: change_mode
+    mode: direct
+- id: restart
+  cron: "30 0 3 * * *"
+  action:
+    type: restart_core
+  enabled: false
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        actions[0].action,
+        ScheduledActionKind::ChangeMode {
+            mode: "direct".into()
+        }
+    );
+    assert_eq!(actions[0].cron_with_seconds().unwrap(), "0 0 18 * * *");
+    assert!(actions[0].is_enabled());
+    assert_eq!(actions[1].action, ScheduledActionKind::RestartCore);
+    assert_eq!(actions[1].cron_with_seconds().unwrap(), "30 0 3 * * *");
+    assert!(!actions[1].is_enabled());
+
+    // the crontab weekdays are translated to the ones of the timer, sunday is 1
+    let cron = |cron: &str| {
+        let action = ScheduledAction {
+            cron: cron.into(),
+            ..actions[0].clone()
+        };
+        action.cron_with_seconds()
+    };
+    assert_eq!(cron("0 18 * * 1-5").unwrap(), "0 0 18 * * 2,3,4,5,6");
+    assert_eq!(cron("0 18 * * 0").unwrap(), "0 0 18 * * 1");
+    assert_eq!(cron("0 18 * * 7").unwrap(), "0 0 18 * * 1");
+    assert_eq!(cron("0 18 * * 5-7").unwrap(), "0 0 18 * * 1,6,7");
+    assert_eq!(cron("0 18 * * sat,sun").unwrap(), "0 0 18 * * 1,7");
+    assert_eq!(cron("0 18 * * */2").unwrap(), "0 0 18 * * 1,3,5,7");
+    assert_eq!(cron("*/15 9-18 1 JAN *").unwrap(), "0 */15 9-18 1 JAN *");
+    assert!(cron("0 18 * *").is_err());
+    assert!(cron("0 24 * * *").is_err());
+    assert!(cron("0 18 * * 8").is_err());
+    assert!(cron("0 18 * * 5-1").is_err());
+    assert!(cron("0 18 * * mon/0").is_err());
+
+    assert!(actions[0].validate().is_ok());
+    let invalid = ScheduledAction {
+        action: ScheduledActionKind::ChangeMode {
+            mode: "direkt".into(),
+        },
+        ..actions[0].clone()
+    };
+    assert!(invalid.validate().is_err());
+}
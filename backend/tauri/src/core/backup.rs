@@ -0,0 +1,575 @@
+//! backup and restore the app state as a zip bundle
+use super::{handle, storage::Storage, CoreManager};
+use crate::{
+    config::{Config, IClashTemp, IProfiles, IVerge},
+    utils::{crypto, dirs, help},
+};
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{Read, Seek, Write},
+    path::Path,
+};
+use zip::{write::FileOptions, ZipArchive, ZipWriter};
+
+/// bump it when the layout of the bundle changes
+pub const BACKUP_VERSION: u32 = 1;
+
+const MANIFEST_FILE: &str = "manifest.json";
+const VERGE_FILE: &str = "verge.yaml";
+const CLASH_FILE: &str = "config.yaml";
+const PROFILES_FILE: &str = "profiles.yaml";
+const PROFILES_DIR: &str = "profiles/";
+const STORAGE_FILE: &str = "storage.json";
+
+/// the keys of the task storage
+const TASK_STORAGE_PREFIX: &str = "task:";
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BackupManifest {
+    pub version: u32,
+    pub app_version: String,
+    /// the timestamp in seconds
+    pub created_at: i64,
+    pub with_storage: bool,
+    /// the salt of the passphrase which encrypts the secrets, none if they are plain
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secrets_salt: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RestoreMode {
+    /// import the profiles and settings of the bundle, keep the current ones
+    Merge,
+    /// replace the whole app state with the bundle
+    Replace,
+}
+
+/// the content of a backup bundle
+#[derive(Debug, Clone)]
+pub struct BackupBundle {
+    pub manifest: BackupManifest,
+    pub verge: IVerge,
+    pub clash: Mapping,
+    pub profiles: IProfiles,
+    /// the files under the profiles dir, file name -> data
+    pub files: HashMap<String, Vec<u8>>,
+    /// the task storage, key -> value
+    pub storage: Option<HashMap<String, String>>,
+}
+
+impl BackupBundle {
+    /// collect the current app state
+    pub fn collect(with_storage: bool) -> Result<Self> {
+        let mut files = HashMap::new();
+        for entry in fs::read_dir(dirs::app_profiles_dir()?)? {
+            let path = entry?.path();
+            if !path.is_file() {
+                continue;
+            }
+            if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+                files.insert(name.to_string(), fs::read(&path)?);
+            }
+        }
+
+        let storage = match with_storage {
+            true => Some(read_task_storage()?),
+            false => None,
+        };
+
+        Ok(Self {
+            manifest: BackupManifest {
+                version: BACKUP_VERSION,
+                app_version: dirs::get_app_version().to_string(),
+                created_at: chrono::Local::now().timestamp(),
+                with_storage,
+                secrets_salt: None,
+            },
+            verge: Config::verge().latest().clone(),
+            clash: Config::clash().latest().0.clone(),
+            profiles: Config::profiles().latest().clone(),
+            files,
+            storage,
+        })
+    }
+
+    /// the secrets are encrypted with the `passphrase` instead of the key of this device,
+    /// so that the bundle could be restored on the other devices
+    pub fn write<W: Write + Seek>(&self, writer: W, passphrase: Option<&str>) -> Result<()> {
+        let mut manifest = self.manifest.clone();
+        let mut profiles = self.profiles.clone();
+        let mut clash = self.clash.clone();
+        let secrets = bundle_secrets(&mut profiles, &mut clash);
+        if secrets.iter().any(|s| crypto::is_encrypted(s)) {
+            bail!("some secrets are not decrypted, please unlock them first");
+        }
+        manifest.secrets_salt = None;
+        if let Some(passphrase) = passphrase.filter(|p| !p.is_empty()) {
+            let salt = crypto::generate_salt();
+            let key = crypto::derive_key(passphrase, &salt)?;
+            for secret in secrets {
+                *secret = crypto::encrypt_with(&key, secret)?;
+            }
+            manifest.secrets_salt = Some(STANDARD.encode(salt));
+        }
+
+        let mut zip = ZipWriter::new(writer);
+        let options = FileOptions::default();
+
+        zip.start_file(MANIFEST_FILE, options)?;
+        zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+        zip.start_file(VERGE_FILE, options)?;
+        zip.write_all(serde_yaml::to_string(&self.verge)?.as_bytes())?;
+        zip.start_file(CLASH_FILE, options)?;
+        zip.write_all(serde_yaml::to_string(&clash)?.as_bytes())?;
+        zip.start_file(PROFILES_FILE, options)?;
+        zip.write_all(serde_yaml::to_string(&profiles)?.as_bytes())?;
+
+        for (name, data) in self.files.iter() {
+            zip.start_file(format!("{PROFILES_DIR}{name}"), options)?;
+            zip.write_all(data)?;
+        }
+        if let Some(storage) = self.storage.as_ref() {
+            zip.start_file(STORAGE_FILE, options)?;
+            zip.write_all(&serde_json::to_vec(storage)?)?;
+        }
+
+        zip.finish()?;
+        Ok(())
+    }
+
+    /// read and validate the bundle, `passphrase` decrypts the secrets
+    pub fn read<R: Read + Seek>(reader: R, passphrase: Option<&str>) -> Result<Self> {
+        let mut zip = ZipArchive::new(reader).context("the backup is not a zip archive")?;
+
+        let mut read_file = |name: &str| -> Result<Vec<u8>> {
+            let mut file = zip
+                .by_name(name)
+                .with_context(|| format!("the backup does not contain `{name}`"))?;
+            let mut data = vec![];
+            file.read_to_end(&mut data)?;
+            Ok(data)
+        };
+
+        let manifest: BackupManifest = serde_json::from_slice(&read_file(MANIFEST_FILE)?)
+            .context("the manifest of the backup is invalid")?;
+        if manifest.version > BACKUP_VERSION {
+            bail!(
+                "the backup is created by a newer version `{}`, please upgrade the app",
+                manifest.app_version
+            );
+        }
+
+        let verge = serde_yaml::from_slice(&read_file(VERGE_FILE)?)
+            .with_context(|| format!("the `{VERGE_FILE}` of the backup is invalid"))?;
+        let mut clash = serde_yaml::from_slice(&read_file(CLASH_FILE)?)
+            .with_context(|| format!("the `{CLASH_FILE}` of the backup is invalid"))?;
+        let mut profiles: IProfiles = serde_yaml::from_slice(&read_file(PROFILES_FILE)?)
+            .with_context(|| format!("the `{PROFILES_FILE}` of the backup is invalid"))?;
+
+        let mut secrets = bundle_secrets(&mut profiles, &mut clash);
+        if let Some(salt) = manifest.secrets_salt.as_ref() {
+            let Some(passphrase) = passphrase.filter(|p| !p.is_empty()) else {
+                bail!("the secrets of the backup are encrypted, please provide the passphrase");
+            };
+            let salt = STANDARD
+                .decode(salt)
+                .context("the salt of the backup is invalid")?;
+            let key = crypto::derive_key(passphrase, &salt)?;
+            for secret in secrets.iter_mut() {
+                **secret = crypto::decrypt_with(&key, secret).context(
+                    "failed to decrypt the secrets of the backup, the passphrase may be wrong",
+                )?;
+            }
+        }
+        // never save the values encrypted by the key of another device
+        if secrets.iter().any(|s| crypto::is_encrypted(s)) {
+            bail!("the secrets of the backup are encrypted by another device");
+        }
+        let storage = match manifest.with_storage {
+            true => Some(
+                serde_json::from_slice(&read_file(STORAGE_FILE)?)
+                    .with_context(|| format!("the `{STORAGE_FILE}` of the backup is invalid"))?,
+            ),
+            false => None,
+        };
+
+        let names = zip
+            .file_names()
+            .filter_map(|n| n.strip_prefix(PROFILES_DIR))
+            .filter(|n| !n.is_empty() && !n.contains(['/', '\\']))
+            .map(String::from)
+            .collect::<Vec<_>>();
+        let mut files = HashMap::new();
+        for name in names {
+            let mut file = zip.by_name(&format!("{PROFILES_DIR}{name}"))?;
+            let mut data = vec![];
+            file.read_to_end(&mut data)?;
+            files.insert(name, data);
+        }
+
+        // every profile item must have its file
+        for item in profiles.items.iter().flatten() {
+            if let Some(file) = item.file.as_ref() {
+                if !files.contains_key(file) {
+                    let name = item.name.as_ref().or(item.uid.as_ref()).cloned();
+                    bail!(
+                        "the file `{file}` of the profile `{}` is missing in the backup",
+                        name.unwrap_or_default()
+                    );
+                }
+            }
+        }
+
+        Ok(Self {
+            manifest,
+            verge,
+            clash,
+            profiles,
+            files,
+            storage,
+        })
+    }
+
+    /// give the items new uids if they collide with the `existing` ones,
+    /// the files and the references in the bundle are renamed as well
+    fn resolve_collisions(&mut self, existing: &HashSet<String>) {
+        let mut used = existing.clone();
+        let mut renamed = HashMap::new();
+
+        for item in self.profiles.items.iter_mut().flatten() {
+            let Some(uid) = item.uid.clone() else {
+                continue;
+            };
+            if used.insert(uid.clone()) {
+                continue;
+            }
+
+            let prefix = uid.get(..1).unwrap_or("d");
+            let new_uid = help::get_uid(prefix);
+            if let Some(file) = item.file.take() {
+                let ext = Path::new(&file)
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .unwrap_or("yaml");
+                let new_file = format!("{new_uid}.{ext}");
+                if let Some(data) = self.files.remove(&file) {
+                    self.files.insert(new_file.clone(), data);
+                }
+                item.file = Some(new_file);
+            }
+            log::info!(target: "app", "rename the profile uid `{uid}` to `{new_uid}` of the backup");
+            item.uid = Some(new_uid.clone());
+            used.insert(new_uid.clone());
+            renamed.insert(uid, new_uid);
+        }
+
+        let rename = |uid: &mut String| {
+            if let Some(new_uid) = renamed.get(uid) {
+                *uid = new_uid.clone();
+            }
+        };
+        self.profiles.current.iter_mut().for_each(&rename);
+        self.profiles.chain.iter_mut().flatten().for_each(&rename);
+        for item in self.profiles.items.iter_mut().flatten() {
+            if let Some(aggregate) = item.aggregate.as_mut() {
+                aggregate.sources.iter_mut().for_each(&rename);
+            }
+            item.chain.iter_mut().flatten().for_each(&rename);
+        }
+    }
+
+    /// apply the bundle to the app state and save the files
+    fn apply(mut self, mode: RestoreMode) -> Result<()> {
+        let profiles_dir = dirs::app_profiles_dir()?;
+
+        let mut stale = vec![];
+        match mode {
+            RestoreMode::Replace => {
+                self.resolve_collisions(&HashSet::new());
+                // the files of the current profiles are removed after the bundle is applied
+                let current = Config::profiles().latest().clone();
+                stale = current
+                    .items
+                    .iter()
+                    .flatten()
+                    .filter_map(|i| i.file.clone())
+                    .filter(|file| !self.files.contains_key(file))
+                    .collect();
+            }
+            RestoreMode::Merge => {
+                let existing = {
+                    let profiles = Config::profiles();
+                    let profiles = profiles.latest();
+                    let items = profiles.items.iter().flatten();
+                    items.filter_map(|i| i.uid.clone()).collect::<HashSet<_>>()
+                };
+                self.resolve_collisions(&existing);
+            }
+        }
+
+        // write the files before the drafts, so no draft is left on the errors
+        for (name, data) in self.files.iter() {
+            fs::write(profiles_dir.join(name), data)
+                .with_context(|| format!("failed to write the profile file `{name}`"))?;
+        }
+        if let Some(storage) = self.storage.as_ref() {
+            write_task_storage(storage, mode == RestoreMode::Replace)?;
+        }
+
+        match mode {
+            RestoreMode::Replace => {
+                *Config::verge().draft() = self.verge;
+                *Config::clash().draft() = IClashTemp(IClashTemp::guard(self.clash));
+                *Config::profiles().draft() = self.profiles;
+            }
+            RestoreMode::Merge => {
+                Config::verge().draft().patch_config(self.verge);
+                Config::clash().draft().patch_config(self.clash);
+                let profiles = Config::profiles();
+                let mut profiles = profiles.draft();
+                if profiles.current.is_none() {
+                    profiles.current = self.profiles.current;
+                }
+                let items = profiles.items.get_or_insert_with(Vec::new);
+                items.extend(self.profiles.items.unwrap_or_default());
+            }
+        }
+
+        Config::verge().apply();
+        Config::clash().apply();
+        Config::profiles().apply();
+        Config::verge().data().save_file()?;
+        Config::clash().data().save_config()?;
+        Config::profiles().data().save_file()?;
+
+        for file in stale {
+            let _ = fs::remove_file(profiles_dir.join(file));
+        }
+        Ok(())
+    }
+}
+
+/// the urls and the credentials of the profiles, and the controller secret
+fn bundle_secrets<'a>(profiles: &'a mut IProfiles, clash: &'a mut Mapping) -> Vec<&'a mut String> {
+    let mut secrets = profiles
+        .items
+        .iter_mut()
+        .flatten()
+        .flat_map(|item| item.secrets_mut())
+        .collect::<Vec<_>>();
+    if let Some(Value::String(secret)) = clash.get_mut("secret") {
+        secrets.push(secret);
+    }
+    secrets
+}
+
+fn read_task_storage() -> Result<HashMap<String, String>> {
+    let db = Storage::global().get_instance();
+    let iter = db.iterator(rocksdb::IteratorMode::From(
+        TASK_STORAGE_PREFIX.as_bytes(),
+        rocksdb::Direction::Forward,
+    ));
+
+    let mut storage = HashMap::new();
+    for item in iter {
+        let (key, value) = item?;
+        if !key.starts_with(TASK_STORAGE_PREFIX.as_bytes()) {
+            break;
+        }
+        storage.insert(
+            String::from_utf8(key.to_vec())?,
+            String::from_utf8(value.to_vec())?,
+        );
+    }
+    Ok(storage)
+}
+
+fn write_task_storage(storage: &HashMap<String, String>, replace: bool) -> Result<()> {
+    let db = Storage::global().get_instance();
+    let tx = db.transaction();
+    if replace {
+        for key in read_task_storage()?.keys() {
+            tx.delete(key.as_bytes())?;
+        }
+    }
+    for (key, value) in storage.iter() {
+        tx.put(key.as_bytes(), value.as_bytes())?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// export the app state to the zip file
+pub fn create_backup(path: &Path, with_storage: bool, passphrase: Option<&str>) -> Result<()> {
+    let bundle = BackupBundle::collect(with_storage)?;
+    let file = fs::File::create(path)
+        .with_context(|| format!("failed to create the backup \"{}\"", path.display()))?;
+    bundle.write(file, passphrase)
+}
+
+/// restore the app state from the zip file and restart the core
+pub async fn restore_backup(
+    path: &Path,
+    mode: RestoreMode,
+    passphrase: Option<&str>,
+) -> Result<()> {
+    let file = fs::File::open(path)
+        .with_context(|| format!("failed to open the backup \"{}\"", path.display()))?;
+    let bundle = BackupBundle::read(file, passphrase)?;
+    log::info!(target: "app", "restore the backup of `{}` in {mode:?} mode", bundle.manifest.app_version);
+    restore_bundle(bundle, mode).await
+}
+
+/// apply the bundle and restart the core
+pub async fn restore_bundle(bundle: BackupBundle, mode: RestoreMode) -> Result<()> {
+    bundle.apply(mode)?;
+
+    Config::generate()?;
+    CoreManager::global().run_core().await?;
+    handle::Handle::refresh_verge();
+    handle::Handle::refresh_clash();
+    handle::Handle::refresh_profiles();
+    Ok(())
+}
+
+#[test]
+fn test_backup_bundle() -> Result<()> {
+    use crate::config::{PrfAggregate, PrfItem};
+    use std::io::Cursor;
+
+    let item = |uid: &str, file: &str| PrfItem {
+        uid: Some(uid.into()),
+        file: Some(file.into()),
+        ..PrfItem::default()
+    };
+    let mut merge = item("m1", "m1.yaml");
+    merge.chain = Some(vec!["s1".into()]);
+    let mut aggregate = item("a1", "a1.yaml");
+    aggregate.aggregate = Some(PrfAggregate {
+        sources: vec!["r1".into(), "r2".into()],
+        ..PrfAggregate::default()
+    });
+
+    let mut bundle = BackupBundle {
+        manifest: BackupManifest {
+            version: BACKUP_VERSION,
+            app_version: "1.0.0".into(),
+            created_at: 0,
+            with_storage: true,
+            secrets_salt: None,
+        },
+        verge: IVerge {
+            enable_tun_mode: Some(true),
+            ..IVerge::default()
+        },
+        clash: serde_yaml::from_str("mode: rule\nsecret: abc")?,
+        profiles: IProfiles {
+            current: Some("r1".into()),
+            chain: Some(vec!["m1".into()]),
+            items: Some(vec![
+                item("r1", "r1.yaml"),
+                item("r2", "r2.yaml"),
+                merge,
+                item("s1", "s1.js"),
+                aggregate,
+            ]),
+            ..IProfiles::default()
+        },
+        files: ["r1.yaml", "r2.yaml", "m1.yaml", "s1.js", "a1.yaml"]
+            .into_iter()
+            .map(|name| (name.to_string(), name.as_bytes().to_vec()))
+            .collect(),
+        storage: Some(HashMap::from([("task:1".into(), "{}".into())])),
+    };
+
+    // the zip round trip
+    let mut data = Cursor::new(vec![]);
+    bundle.write(&mut data, None)?;
+    let read = BackupBundle::read(Cursor::new(data.into_inner()), None)?;
+    assert_eq!(read.manifest.app_version, "1.0.0");
+    assert_eq!(read.verge.enable_tun_mode, Some(true));
+    assert_eq!(read.clash, bundle.clash);
+    assert_eq!(read.files, bundle.files);
+    assert_eq!(read.storage, bundle.storage);
+    assert_eq!(read.profiles.items.map(|i| i.len()), Some(5));
+
+    // the file of a profile is missing
+    let mut broken = bundle.clone();
+    broken.files.remove("s1.js");
+    let mut data = Cursor::new(vec![]);
+    broken.write(&mut data, None)?;
+    assert!(BackupBundle::read(Cursor::new(data.into_inner()), None).is_err());
+
+    // the secrets are encrypted with the passphrase, not the key of the device
+    let mut secret = bundle.clone();
+    let items = secret.profiles.items.as_mut().unwrap();
+    items[0].url = Some("https://example.com/sub?token=secret".into());
+    let mut data = Cursor::new(vec![]);
+    secret.write(&mut data, Some("passphrase"))?;
+    let data = data.into_inner();
+    let read = |passphrase| BackupBundle::read(Cursor::new(data.clone()), passphrase);
+    assert!(read(None).is_err());
+    assert!(read(Some("wrong")).is_err());
+    let restored = read(Some("passphrase"))?;
+    assert_eq!(restored.clash.get("secret"), Some(&Value::from("abc")));
+    assert_eq!(
+        restored.profiles.items.unwrap()[0].url.as_deref(),
+        Some("https://example.com/sub?token=secret")
+    );
+
+    // the values encrypted by the key of another device are refused
+    let key = crypto::derive_key("device", b"shadowrocket-salt")?;
+    let mut other = bundle.clone();
+    other
+        .clash
+        .insert("secret".into(), crypto::encrypt_with(&key, "abc")?.into());
+    assert!(other.write(&mut Cursor::new(vec![]), None).is_err());
+    let mut data = Cursor::new(vec![]);
+    bundle.write(&mut data, None)?;
+    let mut zip = ZipArchive::new(data)?;
+    let mut tampered = ZipWriter::new(Cursor::new(vec![]));
+    for i in 0..zip.len() {
+        let mut file = zip.by_index(i)?;
+        let name = file.name().to_string();
+        let mut buf = vec![];
+        file.read_to_end(&mut buf)?;
+        if name == CLASH_FILE {
+            buf = serde_yaml::to_string(&other.clash)?.into_bytes();
+        }
+        tampered.start_file(name, FileOptions::default())?;
+        tampered.write_all(&buf)?;
+    }
+    let data = tampered.finish()?.into_inner();
+    assert!(BackupBundle::read(Cursor::new(data), None).is_err());
+
+    // the colliding uids are renamed with the references
+    let existing = HashSet::from(["r1".to_string(), "s1".to_string()]);
+    bundle.resolve_collisions(&existing);
+    let items = bundle.profiles.items.as_ref().unwrap();
+    let uid = |index: usize| items[index].uid.clone().unwrap();
+    let (r1, s1) = (uid(0), uid(3));
+    assert!(r1 != "r1" && s1 != "s1");
+    assert_eq!(uid(1), "r2");
+    assert_eq!(bundle.profiles.current.as_ref(), Some(&r1));
+    assert_eq!(items[2].chain, Some(vec![s1.clone()]));
+    assert_eq!(
+        items[4].aggregate.as_ref().unwrap().sources,
+        vec![r1.clone(), "r2".to_string()]
+    );
+
+    let file = items[0].file.clone().unwrap();
+    assert_eq!(file, format!("{r1}.yaml"));
+    assert_eq!(
+        bundle.files.get(&file).map(Vec::as_slice),
+        Some(&b"r1.yaml"[..])
+    );
+    assert!(!bundle.files.contains_key("r1.yaml"));
+    assert!(bundle.files.contains_key(&format!("{s1}.js")));
+    Ok(())
+}
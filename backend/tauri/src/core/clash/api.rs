@@ -89,6 +89,7 @@ impl From<ProxyProviderItem> for ProxyItem {
             vehicle_type: _,
             test_url: _,
             expected_status: _,
+            subscription_info: _,
         } = item;
 
         let now = proxies
@@ -209,6 +210,18 @@ pub struct ProxyProviderItem {
     pub test_url: Option<String>, // Mihomo Only
     #[serde(skip_serializing_if = "Option::is_none")]
     pub expected_status: Option<String>, // Mihomo Only
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subscription_info: Option<SubscriptionInfo>, // Mihomo Only
+}
+
+/// the `subscription-userinfo` of the http provider
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct SubscriptionInfo {
+    pub upload: u64,
+    pub download: u64,
+    pub total: u64,
+    pub expire: u64,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -2,6 +2,7 @@ use super::api;
 use crate::{
     config::{shadowrocket::ClashCore, Config, ConfigType},
     core::logger::Logger,
+    enhance::ValidationError,
     log_err,
     utils::dirs,
 };
@@ -60,6 +61,36 @@ impl CoreManager {
 
     /// 检查配置是否正确
     pub fn check_config(&self) -> Result<()> {
+        // 先检查配置的语义，指出出错的订阅、merge 或 script
+        // 规则的问题只作为警告，交给内核自己检查
+        let errors = { Config::runtime().latest().validation.clone() };
+        let (blocking, warnings): (Vec<_>, Vec<_>) =
+            errors.into_iter().partition(|err| err.kind.is_blocking());
+        let describe = |errors: Vec<ValidationError>| {
+            let profiles = Config::profiles();
+            let profiles = profiles.latest();
+            errors
+                .iter()
+                .map(|err| {
+                    let source = err.source.as_ref().map(|uid| {
+                        let item = profiles.get_item(uid).ok();
+                        item.and_then(|i| i.name.clone()).unwrap_or(uid.clone())
+                    });
+                    match source {
+                        Some(source) => format!("[{source}] {}", err.message),
+                        None => err.message.clone(),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        };
+        if !warnings.is_empty() {
+            log::warn!(target: "app", "{}", describe(warnings));
+        }
+        if !blocking.is_empty() {
+            bail!("{}", describe(blocking));
+        }
+
         let config_path = Config::generate_file(ConfigType::Check)?;
         let config_path = dirs::path_to_str(&config_path)?;
 
@@ -346,28 +377,41 @@ impl CoreManager {
         // 更新配置
         Config::generate()?;
 
-        // 检查配置是否正常
-        self.check_config()?;
+        let result = async {
+            // 检查配置是否正常
+            self.check_config()?;
 
-        // 更新运行时配置
-        let path = Config::generate_file(ConfigType::Run)?;
-        let path = dirs::path_to_str(&path)?;
-
-        // 发送请求 发送5次
-        for i in 0..5 {
-            match api::put_configs(path).await {
-                Ok(_) => break,
-                Err(err) => {
-                    if i < 4 {
-                        log::info!(target: "app", "{err}");
-                    } else {
-                        bail!(err);
+            // 更新运行时配置
+            let path = Config::generate_file(ConfigType::Run)?;
+            let path = dirs::path_to_str(&path)?;
+
+            // 发送请求 发送5次
+            for i in 0..5 {
+                match api::put_configs(path).await {
+                    Ok(_) => break,
+                    Err(err) => {
+                        if i < 4 {
+                            log::info!(target: "app", "{err}");
+                        } else {
+                            bail!(err);
+                        }
                     }
                 }
+                sleep(Duration::from_millis(250)).await;
             }
-            sleep(Duration::from_millis(250)).await;
+            anyhow::Ok(())
         }
+        .await;
 
-        Ok(())
+        // the runtime config is the one running in the core
+        match result {
+            Ok(_) => {
+                Config::runtime().apply();
+            }
+            Err(_) => {
+                Config::runtime().discard();
+            }
+        }
+        result
     }
 }
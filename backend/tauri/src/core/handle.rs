@@ -4,7 +4,7 @@ use anyhow::{bail, Result};
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
 use std::sync::Arc;
-use tauri::{AppHandle, Manager, Window};
+use tauri::{api::notification::Notification, AppHandle, Manager, Window};
 
 #[derive(Debug, Default, Clone)]
 pub struct Handle {
@@ -62,6 +62,15 @@ impl Handle {
         }
     }
 
+    /// show the desktop notification
+    pub fn notify<T: Into<String>, B: Into<String>>(title: T, body: B) {
+        let app_handle = Self::global().app_handle.lock();
+        if let Some(app_handle) = app_handle.as_ref() {
+            let identifier = &app_handle.config().tauri.bundle.identifier;
+            log_err!(Notification::new(identifier).title(title).body(body).show());
+        }
+    }
+
     pub fn update_systray() -> Result<()> {
         let app_handle = Self::global().app_handle.lock();
         if app_handle.is_none() {
@@ -1,14 +1,19 @@
+pub mod backup;
 pub mod clash;
 pub mod commands;
 pub mod handle;
 pub mod hotkey;
 pub mod logger;
 pub mod manager;
+pub mod profile_switch;
+pub mod revisions;
 pub mod storage;
 pub mod sysopt;
 pub mod tasks;
 pub mod tray;
 pub mod updater;
+pub mod watcher;
+pub mod webdav;
 pub mod win_service;
 pub mod win_uwp;
 pub use self::clash::core::*;
@@ -0,0 +1,349 @@
+//! switch the profile and the clash mode by the network environment
+use super::{handle, CoreManager};
+use crate::{
+    config::{shadowrocket::ProfileSwitchRule, Config, IProfiles},
+    feat,
+    utils::config::get_current_clash_mode,
+};
+use anyhow::{bail, Result};
+use ipnet::IpNet;
+use parking_lot::Mutex;
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+/// how often to check the network
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+const DEFAULT_COOLDOWN: u64 = 60;
+
+/// the max delay to retry the failed switch
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct NetworkEnv {
+    pub gateway: Option<IpAddr>,
+    pub interface: Option<String>,
+    pub addrs: Vec<IpAddr>,
+}
+
+impl NetworkEnv {
+    pub fn detect() -> Result<Self> {
+        let (interface, gateway) = default_route()?.unzip();
+        let addrs = if_addrs::get_if_addrs()?
+            .into_iter()
+            .filter(|i| !i.is_loopback())
+            .map(|i| i.ip())
+            .collect();
+        Ok(Self {
+            gateway: gateway.map(IpAddr::V4),
+            interface,
+            addrs,
+        })
+    }
+
+    /// keep the addresses which the rules test only, so the changes of the others,
+    /// e.g. the rotated temporary IPv6 addresses, are not a network change
+    pub fn for_rules(mut self, rules: &[ProfileSwitchRule]) -> Self {
+        let nets = rules
+            .iter()
+            .filter_map(|rule| rule.cidr.as_ref()?.parse::<IpNet>().ok())
+            .collect::<Vec<_>>();
+        self.addrs
+            .retain(|addr| nets.iter().any(|net| net.contains(addr)));
+        self
+    }
+
+    /// the conditions of the rule matched by the env, None if any of them is not matched
+    pub fn matches(&self, rule: &ProfileSwitchRule) -> Option<Vec<String>> {
+        let mut reasons = vec![];
+        if let Some(gateway) = rule.gateway.as_ref() {
+            let gateway = gateway.parse::<IpAddr>().ok()?;
+            if self.gateway != Some(gateway) {
+                return None;
+            }
+            reasons.push(format!("gateway {gateway}"));
+        }
+        if let Some(interface) = rule.interface.as_ref() {
+            if self.interface.as_ref() != Some(interface) {
+                return None;
+            }
+            reasons.push(format!("interface {interface}"));
+        }
+        if let Some(cidr) = rule.cidr.as_ref() {
+            let net = cidr.parse::<IpNet>().ok()?;
+            let addr = self.addrs.iter().find(|addr| net.contains(*addr))?;
+            reasons.push(format!("ip {addr} in {net}"));
+        }
+        // the rule without any condition is never matched
+        (!reasons.is_empty()).then_some(reasons)
+    }
+}
+
+/// the default interface and gateway from `/proc/net/route`
+#[cfg(target_os = "linux")]
+fn default_route() -> Result<Option<(String, Ipv4Addr)>> {
+    let content = std::fs::read_to_string("/proc/net/route")?;
+    Ok(parse_default_route(&content))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn default_route() -> Result<Option<(String, Ipv4Addr)>> {
+    Ok(None)
+}
+
+#[allow(dead_code)]
+fn parse_default_route(content: &str) -> Option<(String, Ipv4Addr)> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            // Iface Destination Gateway Flags RefCnt Use Metric Mask ...
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            if fields.len() < 8 || fields[1] != "00000000" || fields[7] != "00000000" {
+                return None;
+            }
+            // in the byte order of the host
+            let gateway = u32::from_str_radix(fields[2], 16).ok()?;
+            let metric = fields[6].parse::<u32>().ok()?;
+            Some((metric, fields[0], Ipv4Addr::from(gateway.to_ne_bytes())))
+        })
+        .min_by_key(|(metric, ..)| *metric)
+        .map(|(_, iface, gateway)| (iface.to_string(), gateway))
+}
+
+#[derive(Default)]
+struct SwitchState {
+    /// the env which is handled already
+    env: Option<NetworkEnv>,
+    switched_at: Option<Instant>,
+    /// the env whose switch failed, the failed time and the times
+    failed: Option<(NetworkEnv, Instant, u32)>,
+}
+
+/// the exponential backoff of the retries of the failed switch
+fn retry_delay(failures: u32) -> Duration {
+    POLL_INTERVAL
+        .saturating_mul(1 << failures.min(16))
+        .min(MAX_RETRY_DELAY)
+}
+
+pub struct ProfileSwitcher {
+    state: Mutex<SwitchState>,
+}
+
+impl ProfileSwitcher {
+    pub fn global() -> &'static ProfileSwitcher {
+        static SWITCHER: OnceLock<ProfileSwitcher> = OnceLock::new();
+
+        SWITCHER.get_or_init(|| ProfileSwitcher {
+            state: Mutex::new(SwitchState::default()),
+        })
+    }
+
+    pub fn start(&'static self) {
+        tauri::async_runtime::spawn(async move {
+            loop {
+                if let Err(err) = self.check().await {
+                    log::error!(target: "app", "failed to switch the profile: {err}");
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        });
+    }
+
+    async fn check(&self) -> Result<()> {
+        let (rules, cooldown) = {
+            let verge = Config::verge();
+            let verge = verge.latest();
+            if !verge.enable_profile_switch.unwrap_or(false) {
+                // check the env again when it's enabled
+                self.state.lock().env = None;
+                return Ok(());
+            }
+            (
+                verge.profile_switch_rules.clone().unwrap_or_default(),
+                verge.profile_switch_cooldown.unwrap_or(DEFAULT_COOLDOWN),
+            )
+        };
+
+        let env = NetworkEnv::detect()?.for_rules(&rules);
+        {
+            let state = self.state.lock();
+            if state.env.as_ref() == Some(&env) {
+                return Ok(());
+            }
+            // keep the env unhandled until the cooldown is over
+            if let Some(switched_at) = state.switched_at {
+                if switched_at.elapsed() < Duration::from_secs(cooldown) {
+                    return Ok(());
+                }
+            }
+            if let Some((failed_env, failed_at, failures)) = state.failed.as_ref() {
+                if *failed_env == env && failed_at.elapsed() < retry_delay(*failures) {
+                    return Ok(());
+                }
+            }
+        }
+        log::debug!(target: "app", "the network is changed: {env:?}");
+
+        let matched = rules
+            .iter()
+            .find_map(|rule| env.matches(rule).map(|reasons| (rule, reasons)));
+        // the env is handled after the switch succeeds, otherwise it's retried with the backoff
+        let result = match matched {
+            Some((rule, reasons)) => {
+                let reason = format!(
+                    "rule `{}` matched by {}",
+                    rule.display_name(),
+                    reasons.join(", ")
+                );
+                switch(rule, &reason).await
+            }
+            None => Ok(false),
+        };
+        let switched = match result {
+            Ok(switched) => switched,
+            Err(err) => {
+                let failures = {
+                    let mut state = self.state.lock();
+                    let failures = match state.failed.take() {
+                        Some((failed_env, _, failures)) if failed_env == env => failures + 1,
+                        _ => 1,
+                    };
+                    state.failed = Some((env, Instant::now(), failures));
+                    failures
+                };
+                // only notify the first failure of the env
+                if failures == 1 {
+                    handle::Handle::notify("Profile Switch Failed", err.to_string());
+                }
+                return Err(err);
+            }
+        };
+        let mut state = self.state.lock();
+        state.env = Some(env);
+        state.failed = None;
+        if switched {
+            state.switched_at = Some(Instant::now());
+        }
+        Ok(())
+    }
+}
+
+/// apply the profile and the mode of the rule, return whether anything is switched
+async fn switch(rule: &ProfileSwitchRule, reason: &str) -> Result<bool> {
+    let mut switched = vec![];
+
+    let profile = rule.profile.as_ref().filter(|uid| {
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+        profiles.get_current().as_ref() != Some(*uid)
+    });
+    if let Some(uid) = profile {
+        let name = {
+            let profiles = Config::profiles();
+            let profiles = profiles.latest();
+            match profiles.get_item(uid) {
+                Ok(item) => item.name.clone().unwrap_or_else(|| uid.clone()),
+                Err(_) => bail!("the profile `{uid}` of the rule is not found"),
+            }
+        };
+        Config::profiles().draft().patch_config(IProfiles {
+            current: Some(uid.clone()),
+            ..IProfiles::default()
+        })?;
+        match CoreManager::global().update_config().await {
+            Ok(_) => {
+                Config::profiles().apply();
+                Config::profiles().data().save_file()?;
+                handle::Handle::refresh_clash();
+                handle::Handle::refresh_profiles();
+            }
+            Err(err) => {
+                Config::profiles().discard();
+                return Err(err);
+            }
+        }
+        switched.push(format!("profile `{name}`"));
+    }
+
+    if let Some(mode) = rule.mode.as_ref() {
+        if *mode != get_current_clash_mode() {
+            feat::change_clash_mode(mode.clone());
+            switched.push(format!("mode `{mode}`"));
+        }
+    }
+
+    if switched.is_empty() {
+        return Ok(false);
+    }
+    let msg = format!("switch to {}, {reason}", switched.join(" and "));
+    log::info!(target: "app", "{msg}");
+    handle::Handle::notify("Profile Switched", msg);
+    Ok(true)
+}
+
+#[test]
+fn test_retry_delay() {
+    assert_eq!(retry_delay(1), Duration::from_secs(10));
+    assert_eq!(retry_delay(3), Duration::from_secs(40));
+    assert_eq!(retry_delay(10), MAX_RETRY_DELAY);
+    assert_eq!(retry_delay(u32::MAX), MAX_RETRY_DELAY);
+}
+
+#[test]
+fn test_profile_switch_rule() {
+    let route =
+        "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n\
+                 wlan0\t00000000\t0101A8C0\t0003\t0\t0\t600\t00000000\t0\t0\t0\n\
+                 eth0\t00000000\t0100000A\t0003\t0\t0\t100\t00000000\t0\t0\t0\n\
+                 eth0\t0000000A\t00000000\t0001\t0\t0\t100\t000000FF\t0\t0\t0\n";
+    let (interface, gateway) = parse_default_route(route).unwrap();
+    assert_eq!(interface, "eth0");
+    if cfg!(target_endian = "little") {
+        assert_eq!(gateway, Ipv4Addr::new(10, 0, 0, 1));
+    }
+
+    let env = NetworkEnv {
+        gateway: Some(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))),
+        interface: Some("eth0".into()),
+        addrs: vec!["10.0.0.23".parse().unwrap()],
+    };
+    let rule =
+        |gateway: Option<&str>, interface: Option<&str>, cidr: Option<&str>| ProfileSwitchRule {
+            gateway: gateway.map(String::from),
+            interface: interface.map(String::from),
+            cidr: cidr.map(String::from),
+            ..ProfileSwitchRule::default()
+        };
+
+    assert!(env.matches(&rule(None, None, None)).is_none());
+    assert_eq!(
+        env.matches(&rule(Some("10.0.0.1"), Some("eth0"), Some("10.0.0.0/24"))),
+        Some(vec![
+            "gateway 10.0.0.1".to_string(),
+            "interface eth0".to_string(),
+            "ip 10.0.0.23 in 10.0.0.0/24".to_string(),
+        ])
+    );
+    assert!(env
+        .matches(&rule(Some("10.0.0.1"), Some("wlan0"), None))
+        .is_none());
+    assert!(env
+        .matches(&rule(None, None, Some("192.168.0.0/16")))
+        .is_none());
+    assert!(env.matches(&rule(Some("invalid"), None, None)).is_none());
+
+    // only the addresses tested by the rules are compared
+    let mut rotated = env.clone();
+    rotated.addrs.push("2001:db8::1234".parse().unwrap());
+    let rules = [
+        rule(None, None, Some("10.0.0.0/24")),
+        rule(Some("10.0.0.1"), None, None),
+    ];
+    assert_eq!(env.clone().for_rules(&rules), rotated.for_rules(&rules));
+    assert!(env.for_rules(&rules[1..]).addrs.is_empty());
+}
@@ -0,0 +1,228 @@
+//! the revision history of the profile files
+use super::storage::Storage;
+use crate::config::Config;
+use anyhow::{anyhow, Result};
+use rocksdb::{MultiThreaded, TransactionDB};
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
+use std::sync::{Arc, OnceLock};
+
+/// keep 10 revisions of each profile by default
+pub const DEFAULT_MAX_REVISIONS: usize = 10;
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RevisionSource {
+    RemoteUpdate,
+    ManualEdit,
+    Import,
+    /// restored from an older revision
+    Rollback,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ProfileRevision {
+    /// the timestamp in milliseconds, also the revision id
+    pub id: u64,
+    pub uid: String,
+    pub source: RevisionSource,
+    /// the length of the file data
+    pub size: usize,
+    /// the file data, not included in the list
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+}
+
+/// ProfileRevisions is a bridge between the profile files and the storage
+pub struct ProfileRevisions {
+    db: &'static TransactionDB<MultiThreaded>,
+}
+
+impl ProfileRevisions {
+    pub fn global() -> &'static Arc<ProfileRevisions> {
+        static REVISIONS: OnceLock<Arc<ProfileRevisions>> = OnceLock::new();
+
+        REVISIONS.get_or_init(|| {
+            Arc::new(ProfileRevisions {
+                db: Storage::global().get_instance(),
+            })
+        })
+    }
+
+    fn prefix(uid: &str) -> String {
+        format!("profile:revision:{uid}:")
+    }
+
+    fn key(uid: &str, id: u64) -> String {
+        // the padding keeps the revisions sorted in the storage
+        format!("{}{id:020}", Self::prefix(uid))
+    }
+
+    fn max_revisions() -> usize {
+        Config::verge()
+            .latest()
+            .max_profile_revisions
+            .unwrap_or(DEFAULT_MAX_REVISIONS)
+    }
+
+    /// all the revisions of the profile with the data, the oldest first
+    fn load(&self, uid: &str) -> Result<Vec<ProfileRevision>> {
+        let db = self.db;
+        let prefix = Self::prefix(uid);
+        let iter = db.iterator(rocksdb::IteratorMode::From(
+            prefix.as_bytes(),
+            rocksdb::Direction::Forward,
+        ));
+
+        let mut revisions = Vec::new();
+        for item in iter {
+            let (key, mut value) = item?;
+            if !key.starts_with(prefix.as_bytes()) {
+                break;
+            }
+            revisions.push(simd_json::from_slice::<ProfileRevision>(&mut value)?);
+        }
+        Ok(revisions)
+    }
+
+    /// list the revisions of the profile without the data, the newest first
+    pub fn list(&self, uid: &str) -> Result<Vec<ProfileRevision>> {
+        let mut revisions = self.load(uid)?;
+        revisions.reverse();
+        for revision in revisions.iter_mut() {
+            revision.data = None;
+        }
+        Ok(revisions)
+    }
+
+    /// get the revision with the data
+    pub fn get(&self, uid: &str, id: u64) -> Result<ProfileRevision> {
+        let db = self.db;
+        let mut value = db
+            .get(Self::key(uid, id).as_bytes())?
+            .ok_or(anyhow!("failed to find the revision \"{id}\" of \"{uid}\""))?;
+        Ok(simd_json::from_slice(&mut value)?)
+    }
+
+    /// record a new revision of the profile file,
+    /// it's skipped if the data is the same as the latest one
+    pub fn record(&self, uid: &str, source: RevisionSource, data: &str) -> Result<()> {
+        self.record_with_max(uid, source, data, Self::max_revisions())
+    }
+
+    /// record the revision and keep at most `max` revisions
+    fn record_with_max(
+        &self,
+        uid: &str,
+        source: RevisionSource,
+        data: &str,
+        max: usize,
+    ) -> Result<()> {
+        if max == 0 {
+            return Ok(());
+        }
+
+        let revisions = self.load(uid)?;
+        let latest = revisions.last();
+        if latest.and_then(|r| r.data.as_deref()) == Some(data) {
+            return Ok(());
+        }
+
+        let now = chrono::Local::now().timestamp_millis() as u64;
+        let id = latest.map_or(now, |r| now.max(r.id + 1));
+        let revision = ProfileRevision {
+            id,
+            uid: uid.to_string(),
+            source,
+            size: data.len(),
+            data: Some(data.to_string()),
+        };
+
+        let db = self.db;
+        let tx = db.transaction();
+        tx.put(Self::key(uid, id).as_bytes(), simd_json::to_vec(&revision)?)?;
+        // drop the oldest revisions out of the limit
+        let overflow = (revisions.len() + 1).saturating_sub(max);
+        for old in revisions.iter().take(overflow) {
+            tx.delete(Self::key(uid, old.id).as_bytes())?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// keep the data before overwriting if there is no revision yet,
+    /// so that the first change could also be rolled back
+    pub fn record_base(&self, uid: &str, data: &str) -> Result<()> {
+        match self.load(uid)?.is_empty() {
+            true => self.record(uid, RevisionSource::Import, data),
+            false => Ok(()),
+        }
+    }
+
+    /// the unified diff between two revisions
+    pub fn diff(&self, uid: &str, from: u64, to: u64) -> Result<String> {
+        let old = self.get(uid, from)?.data.unwrap_or_default();
+        let new = self.get(uid, to)?.data.unwrap_or_default();
+        let diff = TextDiff::from_lines(&old, &new)
+            .unified_diff()
+            .context_radius(3)
+            .header(&from.to_string(), &to.to_string())
+            .to_string();
+        Ok(diff)
+    }
+
+    /// remove all the revisions of the profile
+    pub fn clear(&self, uid: &str) -> Result<()> {
+        let db = self.db;
+        let tx = db.transaction();
+        for revision in self.load(uid)? {
+            tx.delete(Self::key(uid, revision.id).as_bytes())?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_profile_revisions() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let db = TransactionDB::<MultiThreaded>::open_default(dir.path())?;
+    let revisions = ProfileRevisions {
+        db: Box::leak(Box::new(db)),
+    };
+    let record = |data: &str| revisions.record_with_max("a", RevisionSource::ManualEdit, data, 3);
+    let data = |list: &[ProfileRevision]| {
+        list.iter()
+            .map(|r| revisions.get("a", r.id).unwrap().data.unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    // disabled
+    revisions.record_with_max("a", RevisionSource::Import, "v0", 0)?;
+    assert!(revisions.list("a")?.is_empty());
+
+    // the same data as the latest one is skipped
+    record("v1\n")?;
+    record("v1\n")?;
+    assert_eq!(revisions.list("a")?.len(), 1);
+
+    // the oldest ones are dropped, the newest first
+    for v in ["v2\n", "v3\n", "v4\n"] {
+        record(v)?;
+    }
+    let list = revisions.list("a")?;
+    assert_eq!(data(&list), ["v4\n", "v3\n", "v2\n"]);
+    assert!(list.iter().all(|r| r.data.is_none() && r.size == 3));
+    assert!(list[0].id > list[1].id && list[1].id > list[2].id);
+    // the other profile is not affected
+    assert!(revisions.list("b")?.is_empty());
+
+    let diff = revisions.diff("a", list[1].id, list[0].id)?;
+    assert!(diff.contains("-v3"));
+    assert!(diff.contains("+v4"));
+    assert!(revisions.diff("a", 0, list[0].id).is_err());
+
+    revisions.clear("a")?;
+    assert!(revisions.list("a")?.is_empty());
+    Ok(())
+}
@@ -13,17 +13,20 @@ use dyn_clone::{clone_trait_object, DynClone};
 /// #[derive(Clone)]
 /// pub struct HelloJob {}
 /// impl JobExecutor for HelloJob {
-///     fn execute(&self) -> Result<()> {
+///     fn execute(&self) -> Result<TaskOutput> {
 ///        println!("hello");
-///       Ok(())
+///       Ok(None)
 ///    }
 /// }
 /// ```
 /// Then you can pass it to the task manager to execute it.
 ///
 ///
+/// the output of the job, which is kept in the result of the task event
+pub type TaskOutput = Option<serde_json::Value>;
+
 pub trait JobExecutor: DynClone {
-    fn execute(&self) -> Result<()>;
+    fn execute(&self) -> Result<TaskOutput>;
 }
 
 clone_trait_object!(JobExecutor);
@@ -32,7 +35,7 @@ pub type Job = Box<dyn JobExecutor + Send + Sync>;
 
 #[async_trait]
 pub trait AsyncJobExecutor: DynClone {
-    async fn execute(&self) -> Result<()>;
+    async fn execute(&self) -> Result<TaskOutput>;
 }
 
 clone_trait_object!(AsyncJobExecutor);
@@ -76,14 +79,14 @@ impl From<AsyncJob> for TaskExecutor {
 struct DefaultJobExecutor {}
 
 impl JobExecutor for DefaultJobExecutor {
-    fn execute(&self) -> Result<()> {
+    fn execute(&self) -> Result<TaskOutput> {
         unimplemented!("not implemented");
     }
 }
 
 #[async_trait]
 impl AsyncJobExecutor for DefaultJobExecutor {
-    async fn execute(&self) -> Result<()> {
+    async fn execute(&self) -> Result<TaskOutput> {
         unimplemented!("not implemented");
     }
 }
@@ -0,0 +1,166 @@
+use super::super::{
+    executor::{AsyncJobExecutor, TaskExecutor, TaskOutput},
+    task::{Task, TaskID, TaskManager, TaskSchedule},
+};
+use crate::{
+    config::{
+        shadowrocket::{ScheduledAction, ScheduledActionKind},
+        Config, IVerge,
+    },
+    core::{clash::api, handle, CoreManager},
+    feat,
+};
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde_yaml::Mapping;
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    sync::{Arc, OnceLock},
+};
+
+const INITIAL_TASK_ID: TaskID = 20000000; // 避免和其他任务的 ID 冲突
+
+type ActionID = String;
+
+#[derive(Clone)]
+pub struct ScheduledActionRunner(ScheduledAction);
+
+#[async_trait]
+impl AsyncJobExecutor for ScheduledActionRunner {
+    async fn execute(&self) -> Result<TaskOutput> {
+        let name = self.0.name.as_deref().unwrap_or(&self.0.id);
+        log::info!(target: "app", "running scheduled action `{name}`");
+        match &self.0.action {
+            ScheduledActionKind::ChangeMode { mode } => {
+                let mut mapping = Mapping::new();
+                mapping.insert("mode".into(), mode.as_str().into());
+                api::patch_configs(&mapping).await?;
+                feat::patch_clash(mapping).await?;
+                handle::Handle::refresh_clash();
+            }
+            ScheduledActionKind::EnableSystemProxy | ScheduledActionKind::DisableSystemProxy => {
+                let enable = self.0.action == ScheduledActionKind::EnableSystemProxy;
+                feat::patch_verge(IVerge {
+                    enable_system_proxy: Some(enable),
+                    ..IVerge::default()
+                })
+                .await?;
+                handle::Handle::refresh_verge();
+            }
+            ScheduledActionKind::UpdateProfiles => {
+                let updated = update_profiles().await?;
+                return Ok(Some(serde_json::to_value(updated)?));
+            }
+            ScheduledActionKind::RestartCore => {
+                CoreManager::global().run_core().await?;
+                handle::Handle::refresh_clash();
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// update all the remote profiles, return the updated uids
+async fn update_profiles() -> Result<Vec<String>> {
+    let uids = Config::profiles()
+        .latest()
+        .get_items()
+        .map(|items| {
+            items
+                .iter()
+                .filter(|item| item.itype.as_deref() == Some("remote"))
+                .filter_map(|item| item.uid.clone())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let mut updated = vec![];
+    let mut errors = vec![];
+    for uid in uids {
+        match feat::update_profile(uid.clone(), None).await {
+            Ok(_) => updated.push(uid),
+            Err(err) => errors.push(format!("{uid}: {err}")),
+        }
+    }
+    if !errors.is_empty() {
+        bail!("failed to update the profiles, {}", errors.join("; "));
+    }
+    Ok(updated)
+}
+
+pub struct ScheduledActionsGuard {
+    task_map: HashMap<ActionID, (TaskID, ScheduledAction)>,
+}
+
+impl ScheduledActionsGuard {
+    pub fn global() -> &'static Arc<Mutex<Self>> {
+        static GUARD: OnceLock<Arc<Mutex<ScheduledActionsGuard>>> = OnceLock::new();
+
+        GUARD.get_or_init(|| {
+            Arc::new(Mutex::new(Self {
+                task_map: HashMap::new(),
+            }))
+        })
+    }
+
+    /// sync the tasks with the actions in the verge config
+    pub fn refresh(&mut self) {
+        let actions = Config::verge()
+            .latest()
+            .scheduled_actions
+            .clone()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|action| action.is_enabled())
+            .map(|action| (action.id.clone(), action))
+            .collect::<HashMap<_, _>>();
+
+        let mut task_manager = TaskManager::global().write();
+        self.task_map.retain(|id, (task_id, action)| {
+            let keep = actions.get(id) == Some(action);
+            if !keep {
+                crate::log_err!(task_manager.remove_task(*task_id));
+            }
+            keep
+        });
+
+        for (id, action) in actions {
+            if self.task_map.contains_key(&id) {
+                continue;
+            }
+            let task_id = get_task_id(&id);
+            let task = new_task(task_id, &action);
+            match task.and_then(|task| task_manager.add_task(task)) {
+                Ok(_) => {
+                    self.task_map.insert(id, (task_id, action));
+                }
+                Err(err) => {
+                    log::error!(target: "app", "failed to schedule the action `{id}`: {err}");
+                }
+            }
+        }
+    }
+}
+
+fn get_task_id(id: &str) -> TaskID {
+    let mut hash = DefaultHasher::new();
+    format!("scheduled-action-{id}").hash(&mut hash);
+    let task_id = hash.finish();
+    if task_id < INITIAL_TASK_ID {
+        INITIAL_TASK_ID + task_id
+    } else {
+        task_id
+    }
+}
+
+fn new_task(task_id: TaskID, action: &ScheduledAction) -> Result<Task> {
+    Ok(Task {
+        id: task_id,
+        name: format!("scheduled-action-{}", action.id),
+        executor: TaskExecutor::Async(Box::new(ScheduledActionRunner(action.clone()))),
+        schedule: TaskSchedule::Cron(action.cron_with_seconds()?),
+        ..Task::default()
+    })
+}
@@ -2,7 +2,7 @@ use super::JobExt;
 use crate::{
     config::Config,
     core::tasks::{
-        executor::{AsyncJobExecutor, TaskExecutor},
+        executor::{AsyncJobExecutor, TaskExecutor, TaskOutput},
         task::TaskSchedule,
     },
     utils::dirs,
@@ -90,8 +90,9 @@ pub fn clear_logs() -> Result<()> {
 
 #[async_trait]
 impl AsyncJobExecutor for ClearLogsJob {
-    async fn execute(&self) -> Result<()> {
-        clear_logs()
+    async fn execute(&self) -> Result<TaskOutput> {
+        clear_logs()?;
+        Ok(None)
     }
 }
 
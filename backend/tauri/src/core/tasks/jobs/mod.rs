@@ -1,5 +1,8 @@
+mod actions;
 mod logger;
 mod profiles;
+mod subscription;
+mod webdav;
 
 use super::{
     task::Task,
@@ -7,6 +10,7 @@ use super::{
 };
 use anyhow::anyhow;
 use parking_lot::Mutex;
+pub use actions::ScheduledActionsGuard;
 pub use profiles::ProfilesJobGuard;
 use std::sync::{Arc, OnceLock};
 pub trait JobExt {
@@ -26,7 +30,9 @@ impl JobsManager {
 
     pub fn global_register() -> Result<()> {
         let jobs: Vec<Box<dyn JobExt + Send + Sync>> = vec![
-        // Box::<logger::ClearLogsJob>::default() as Box<dyn JobExt + Send + Sync>
+            // Box::<logger::ClearLogsJob>::default() as Box<dyn JobExt + Send + Sync>
+            Box::<subscription::SubscriptionAlertJob>::default(),
+            Box::<webdav::WebDavSyncJob>::default(),
         ];
         for job in jobs {
             let task = job.setup();
@@ -46,10 +52,14 @@ impl ConfigChangedNotifier for JobsManager {
             .iter()
             .find(|job| job.name() == job_name)
             .ok_or(anyhow!("job not exist"))?;
-        let task = job.setup();
-        if let Some(task) = task {
-            let mut task_manager = super::task::TaskManager::global().write();
-            task_manager.remove_task(task.id)?;
+        let mut task_manager = super::task::TaskManager::global().write();
+        // the id of the new task is assigned when it's added, so find the old one by name
+        let old = task_manager.list().into_iter().find(|t| t.name == job_name);
+        if let Some(old) = old {
+            task_manager.remove_task(old.id)?;
+        }
+        // the job may be disabled by the new config
+        if let Some(task) = job.setup() {
             task_manager.add_task(task)?;
         }
         Ok(())
@@ -1,5 +1,5 @@
 use super::super::{
-    executor::{AsyncJobExecutor, TaskExecutor},
+    executor::{AsyncJobExecutor, TaskExecutor, TaskOutput},
     task::{Task, TaskID, TaskManager, TaskSchedule},
 };
 use crate::{config::Config, feat};
@@ -30,10 +30,11 @@ impl ProfileUpdater {
 
 #[async_trait]
 impl AsyncJobExecutor for ProfileUpdater {
-    async fn execute(&self) -> Result<()> {
+    async fn execute(&self) -> Result<TaskOutput> {
         log::info!(target: "app", "running timer task `{}`", self.0);
         match feat::update_profile(self.0.clone(), None).await {
-            Ok(_) => Ok(()),
+            // keep the changes of the subscription in the event result
+            Ok(changes) => Ok(changes.map(serde_json::to_value).transpose()?),
             Err(err) => {
                 log::error!(target: "app", "failed to update profile: {err}");
                 Err(err)
@@ -0,0 +1,244 @@
+use super::JobExt;
+use crate::{
+    config::Config,
+    core::{
+        clash::api,
+        handle,
+        tasks::{
+            executor::{AsyncJobExecutor, TaskExecutor, TaskOutput},
+            task::{Task, TaskSchedule},
+        },
+    },
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+const SUBSCRIPTION_ALERT_TASK_NAME: &str = "subscription_alert";
+
+pub const DEFAULT_TRAFFIC_ALERT_PERCENT: u8 = 90;
+pub const DEFAULT_EXPIRE_ALERT_DAYS: u64 = 3;
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum AlertKind {
+    Traffic,
+    Expire,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct SubscriptionAlert {
+    /// the uid of the profile or the name of the proxy provider
+    pub source: String,
+    pub name: String,
+    pub kind: AlertKind,
+    pub message: String,
+}
+
+/// the traffic and the expiry of a subscription, same as `subscription-userinfo`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SubscriptionUsage {
+    pub upload: u64,
+    pub download: u64,
+    pub total: u64,
+    /// the timestamp in seconds, 0 means never
+    pub expire: u64,
+}
+
+impl SubscriptionUsage {
+    /// check the usage against the thresholds, 0 to disable the check
+    pub fn check(&self, percent: u8, days: u64, now: u64) -> Vec<(AlertKind, String)> {
+        let mut alerts = vec![];
+
+        let used = self.upload + self.download;
+        if percent > 0 && self.total > 0 && used * 100 >= self.total * percent as u64 {
+            alerts.push((
+                AlertKind::Traffic,
+                format!(
+                    "{}% of the traffic is used ({} / {})",
+                    used * 100 / self.total,
+                    format_bytes(used),
+                    format_bytes(self.total)
+                ),
+            ));
+        }
+
+        if days > 0 && self.expire > 0 {
+            if self.expire <= now {
+                alerts.push((AlertKind::Expire, "the subscription is expired".into()));
+            } else if self.expire - now <= days * 86400 {
+                let remain = (self.expire - now).div_ceil(86400);
+                alerts.push((
+                    AlertKind::Expire,
+                    format!("the subscription expires in {remain} days"),
+                ));
+            }
+        }
+        alerts
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.2} {}", UNITS[unit])
+}
+
+/// the alerts which are notified, so that they are not notified again
+fn notified() -> &'static Arc<Mutex<HashSet<(String, AlertKind)>>> {
+    static NOTIFIED: OnceLock<Arc<Mutex<HashSet<(String, AlertKind)>>>> = OnceLock::new();
+    NOTIFIED.get_or_init(|| Arc::new(Mutex::new(HashSet::new())))
+}
+
+/// check the remote profiles and the proxy providers of the core
+pub async fn check_subscriptions() -> Vec<SubscriptionAlert> {
+    let (percent, days) = {
+        let verge = Config::verge();
+        let verge = verge.latest();
+        (
+            verge
+                .traffic_alert_percent
+                .unwrap_or(DEFAULT_TRAFFIC_ALERT_PERCENT),
+            verge.expire_alert_days.unwrap_or(DEFAULT_EXPIRE_ALERT_DAYS),
+        )
+    };
+    let now = chrono::Local::now().timestamp() as u64;
+
+    // (source, name, usage)
+    let mut usages = vec![];
+    if let Some(items) = Config::profiles().latest().get_items() {
+        for item in items
+            .iter()
+            .filter(|i| i.itype.as_deref() == Some("remote"))
+        {
+            let (Some(uid), Some(extra)) = (item.uid.as_ref(), item.extra.as_ref()) else {
+                continue;
+            };
+            let usage = SubscriptionUsage {
+                upload: extra.upload as u64,
+                download: extra.download as u64,
+                total: extra.total as u64,
+                expire: extra.expire as u64,
+            };
+            let name = item.name.clone().unwrap_or(uid.clone());
+            usages.push((uid.clone(), name, usage));
+        }
+    }
+
+    // the subscription info of the http providers, Mihomo Only
+    match api::get_providers_proxies().await {
+        Ok(res) => {
+            for (name, provider) in res.providers {
+                let Some(info) = provider.subscription_info else {
+                    continue;
+                };
+                let usage = SubscriptionUsage {
+                    upload: info.upload,
+                    download: info.download,
+                    total: info.total,
+                    expire: info.expire,
+                };
+                usages.push((name.clone(), name, usage));
+            }
+        }
+        Err(err) => log::debug!(target: "app", "failed to get the providers: {err}"),
+    }
+
+    let mut alerts = vec![];
+    for (source, name, usage) in usages {
+        for (kind, message) in usage.check(percent, days, now) {
+            alerts.push(SubscriptionAlert {
+                source: source.clone(),
+                name: name.clone(),
+                kind,
+                message,
+            });
+        }
+    }
+    alerts
+}
+
+#[derive(Clone, Default)]
+pub struct SubscriptionAlertJob;
+
+#[async_trait]
+impl AsyncJobExecutor for SubscriptionAlertJob {
+    async fn execute(&self) -> Result<TaskOutput> {
+        let alerts = check_subscriptions().await;
+
+        // only notify the new alerts, the resolved ones could be notified again
+        let mut notified = notified().lock();
+        notified.retain(|key| {
+            alerts
+                .iter()
+                .any(|a| (&a.source, a.kind) == (&key.0, key.1))
+        });
+        for alert in alerts.iter() {
+            if notified.insert((alert.source.clone(), alert.kind)) {
+                log::warn!(target: "app", "subscription alert `{}`: {}", alert.name, alert.message);
+                handle::Handle::notify(&alert.name, &alert.message);
+            }
+        }
+
+        // keep the alerts in the task event
+        match alerts.is_empty() {
+            true => Ok(None),
+            false => Ok(Some(serde_json::to_value(&alerts)?)),
+        }
+    }
+}
+
+impl JobExt for SubscriptionAlertJob {
+    fn name(&self) -> &'static str {
+        SUBSCRIPTION_ALERT_TASK_NAME
+    }
+
+    fn setup(&self) -> Option<Task> {
+        Some(Task {
+            name: SUBSCRIPTION_ALERT_TASK_NAME.to_string(),
+            schedule: TaskSchedule::Interval(Duration::from_secs(60 * 60)), // 每小时检查一次
+            executor: TaskExecutor::Async(Box::new(self.clone())),
+            ..Default::default()
+        })
+    }
+}
+
+#[test]
+fn test_subscription_usage() {
+    let gb = 1024 * 1024 * 1024;
+    let now = 1_700_000_000;
+    let usage = SubscriptionUsage {
+        upload: gb,
+        download: 90 * gb,
+        total: 100 * gb,
+        expire: now + 86400 * 2 + 1,
+    };
+
+    let alerts = usage.check(90, 3, now);
+    assert_eq!(alerts.len(), 2);
+    assert_eq!(alerts[0].0, AlertKind::Traffic);
+    assert_eq!(
+        alerts[0].1,
+        "91% of the traffic is used (91.00 GB / 100.00 GB)"
+    );
+    assert_eq!(alerts[1].1, "the subscription expires in 3 days");
+
+    assert!(usage.check(95, 2, now).is_empty());
+    assert!(usage.check(0, 0, now).is_empty());
+    let expired = SubscriptionUsage {
+        expire: now,
+        ..usage
+    };
+    assert_eq!(expired.check(0, 1, now)[0].1, "the subscription is expired");
+}
@@ -0,0 +1,47 @@
+use super::JobExt;
+use crate::{
+    config::Config,
+    core::{
+        tasks::{
+            executor::{AsyncJobExecutor, TaskExecutor, TaskOutput},
+            task::{Task, TaskSchedule},
+        },
+        webdav,
+    },
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::time::Duration;
+
+pub const WEBDAV_SYNC_TASK_NAME: &str = "webdav_sync";
+
+#[derive(Clone, Default)]
+pub struct WebDavSyncJob;
+
+#[async_trait]
+impl AsyncJobExecutor for WebDavSyncJob {
+    async fn execute(&self) -> Result<TaskOutput> {
+        let action = webdav::sync(None).await?;
+        Ok(Some(serde_json::to_value(action)?))
+    }
+}
+
+impl JobExt for WebDavSyncJob {
+    fn name(&self) -> &'static str {
+        WEBDAV_SYNC_TASK_NAME
+    }
+
+    fn setup(&self) -> Option<Task> {
+        let verge = Config::verge();
+        let verge = verge.latest();
+        verge.webdav_url.as_ref().filter(|u| !u.is_empty())?;
+        // in minutes, 0 to disable the auto sync
+        let interval = verge.webdav_sync_interval.filter(|i| *i > 0)?;
+        Some(Task {
+            name: WEBDAV_SYNC_TASK_NAME.to_string(),
+            schedule: TaskSchedule::Interval(Duration::from_secs(interval * 60)),
+            executor: TaskExecutor::Async(Box::new(self.clone())),
+            ..Default::default()
+        })
+    }
+}
@@ -6,3 +6,4 @@ pub mod task;
 mod utils;
 
 pub use jobs::JobsManager;
+pub use utils::ConfigChangedNotifier;
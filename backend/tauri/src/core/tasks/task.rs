@@ -33,6 +33,8 @@ pub enum TaskState {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum TaskRunResult {
     Ok,
+    /// finished with the output of the job
+    Output(serde_json::Value),
     Err(String),
 }
 
@@ -40,8 +42,7 @@ pub enum TaskRunResult {
 pub enum TaskSchedule {
     Once(Duration),     // 一次性执行
     Interval(Duration), // 按间隔执行
-    #[allow(dead_code)]
-    Cron(String), // 按 cron 表达式执行
+    Cron(String),       // 按 cron 表达式执行
 }
 
 impl Default for TaskSchedule {
@@ -186,7 +187,8 @@ fn wrap_job(list: TaskList, mut id_generator: SnowflakeIdGenerator, task_id: Tas
     let res = job.execute();
     {
         let res = match res {
-            Ok(_) => TaskRunResult::Ok,
+            Ok(None) => TaskRunResult::Ok,
+            Ok(Some(output)) => TaskRunResult::Output(output),
             Err(e) => {
                 error!(format!("task error: {}", e.to_string()));
                 TaskRunResult::Err(e.to_string())
@@ -220,7 +222,8 @@ async fn wrap_async_job(
     let res = async_job.execute().await;
     {
         let res = match res {
-            Ok(_) => TaskRunResult::Ok,
+            Ok(None) => TaskRunResult::Ok,
+            Ok(Some(output)) => TaskRunResult::Output(output),
             Err(e) => {
                 error!(format!("task error: {}", e.to_string()));
                 TaskRunResult::Err(e.to_string())
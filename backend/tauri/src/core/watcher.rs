@@ -0,0 +1,140 @@
+//! watch the profile files for the external edits, e.g. by `view_profile`
+use super::{handle, CoreManager};
+use crate::{
+    config::{Config, PrfItem},
+    enhance,
+    utils::{dirs, help},
+};
+use anyhow::{bail, Result};
+use notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use parking_lot::Mutex;
+use std::{collections::HashSet, path::PathBuf, sync::OnceLock, time::Duration};
+
+/// wait for the editor to finish writing
+const DEBOUNCE_TIMEOUT: Duration = Duration::from_millis(500);
+
+pub struct ProfilesWatcher {
+    debouncer: Mutex<Option<Debouncer<RecommendedWatcher>>>,
+}
+
+impl ProfilesWatcher {
+    pub fn global() -> &'static ProfilesWatcher {
+        static WATCHER: OnceLock<ProfilesWatcher> = OnceLock::new();
+
+        WATCHER.get_or_init(|| ProfilesWatcher {
+            debouncer: Mutex::new(None),
+        })
+    }
+
+    pub fn start(&self) -> Result<()> {
+        let mut debouncer = self.debouncer.lock();
+        if debouncer.is_some() {
+            return Ok(());
+        }
+
+        let mut watcher = new_debouncer(DEBOUNCE_TIMEOUT, |res: DebounceEventResult| match res {
+            Ok(events) => {
+                let paths = events.into_iter().map(|e| e.path).collect::<Vec<_>>();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(err) = on_changed(paths).await {
+                        log::error!(target: "app", "failed to apply the profile changes: {err}");
+                    }
+                });
+            }
+            Err(err) => log::error!(target: "app", "profiles watcher error: {err}"),
+        })?;
+        watcher
+            .watcher()
+            .watch(&dirs::app_profiles_dir()?, RecursiveMode::NonRecursive)?;
+
+        *debouncer = Some(watcher);
+        Ok(())
+    }
+}
+
+/// the items of the current profile and the active chain, whose files are changed
+fn changed_items(paths: &[PathBuf]) -> Vec<PrfItem> {
+    let files = paths
+        .iter()
+        .filter_map(|p| p.file_name()?.to_str().map(String::from))
+        .collect::<HashSet<_>>();
+
+    let profiles = Config::profiles();
+    let profiles = profiles.latest();
+    let mut active = profiles.active_chain();
+    active.extend(profiles.get_current());
+
+    active
+        .iter()
+        .filter_map(|uid| profiles.get_item(uid).ok())
+        .filter(|item| item.file.as_ref().map_or(false, |f| files.contains(f)))
+        .cloned()
+        .collect()
+}
+
+/// check the file before applying it, the running core is kept if failed
+fn check_item(item: &PrfItem) -> Result<()> {
+    let file = item.file.clone().unwrap_or_default();
+    let path = dirs::app_profiles_dir()?.join(&file);
+    let name = item.name.as_deref().unwrap_or(&file);
+    match item.itype.as_deref() {
+        Some("script") => {
+            if let Err(err) = std::fs::read_to_string(&path) {
+                bail!("failed to read `{name}`: {err}");
+            }
+        }
+        _ => {
+            if let Err(err) = help::read_merge_mapping(&path) {
+                bail!("failed to parse `{name}`: {err:#}");
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn on_changed(paths: Vec<PathBuf>) -> Result<()> {
+    let items = changed_items(&paths);
+    if items.is_empty() {
+        return Ok(());
+    }
+
+    for item in items.iter() {
+        if let Err(err) = check_item(item) {
+            handle::Handle::notice_message("set_config::error", format!("{err}"));
+            return Err(err);
+        }
+    }
+
+    // enhance without the runtime draft, which is generated by `update_config` only
+    let (new, _, logs, _) = enhance::enhance();
+    let exceptions = items
+        .iter()
+        .filter_map(|item| logs.get(item.uid.as_ref()?))
+        .flatten()
+        .filter(|(level, _)| level == "exception")
+        .map(|(_, msg)| msg.clone())
+        .collect::<Vec<_>>();
+    if !exceptions.is_empty() {
+        let msg = exceptions.join("\n");
+        handle::Handle::notice_message("set_config::error", &msg);
+        bail!(msg);
+    }
+    // skip the changes which are applied already, e.g. written by the app itself
+    if Config::runtime().data().config.as_ref() == Some(&new) {
+        return Ok(());
+    }
+
+    log::info!(target: "app", "the profile files are changed, update the config");
+    match CoreManager::global().update_config().await {
+        Ok(_) => {
+            handle::Handle::refresh_clash();
+            handle::Handle::notice_message("set_config::ok", "ok");
+            Ok(())
+        }
+        Err(err) => {
+            handle::Handle::notice_message("set_config::error", format!("{err}"));
+            Err(err)
+        }
+    }
+}
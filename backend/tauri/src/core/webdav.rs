@@ -0,0 +1,529 @@
+//! sync the profiles and settings with a WebDAV server
+use super::{
+    backup::{self, BackupBundle, RestoreMode},
+    handle,
+    storage::Storage,
+};
+use crate::config::{Config, IVerge};
+use anyhow::{bail, Result};
+use reqwest::{
+    header::{ETAG, IF_MATCH, IF_NONE_MATCH, LAST_MODIFIED},
+    Method, RequestBuilder, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::{collections::HashSet, io::Cursor, time::Duration};
+
+/// the name of the bundle on the server
+const SYNC_FILE: &str = "shadowrocket-sync.zip";
+
+/// the timeouts of the requests to the server
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// the key of the last synced state in the storage
+const SYNC_STATE_KEY: &str = "sync:webdav:state";
+
+/// the types of the items whose files are fetched or generated, not authored by the user
+const GENERATED_TYPES: [&str; 2] = ["remote", "aggregate"];
+
+/// the fields of `IVerge` which are always kept on this device
+const DEVICE_FIELDS: [&str; 16] = [
+    "app_singleton_port",
+    "enable_tun_mode",
+    "enable_service_mode",
+    "enable_auto_launch",
+    "enable_silent_start",
+    "enable_system_proxy",
+    "enable_proxy_guard",
+    "system_proxy_bypass",
+    "window_size_position",
+    "window_size_state",
+    "verge_mixed_port",
+    "webdav_url",
+    "webdav_username",
+    "webdav_password",
+    "webdav_sync_passphrase",
+    "webdav_sync_exclude",
+];
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncAction {
+    /// both sides are not changed
+    None,
+    Upload,
+    Download,
+    /// both sides are changed since the last sync
+    Conflict,
+}
+
+/// the state of the last sync
+#[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct SyncState {
+    /// the etag of the remote bundle
+    pub etag: Option<String>,
+    /// the checksum of the local data
+    pub checksum: Option<u32>,
+}
+
+impl SyncState {
+    fn load() -> Result<Self> {
+        let db = Storage::global().get_instance();
+        match db.get(SYNC_STATE_KEY.as_bytes())? {
+            Some(mut value) => Ok(simd_json::from_slice(&mut value)?),
+            None => Ok(Self::default()),
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let db = Storage::global().get_instance();
+        db.put(SYNC_STATE_KEY.as_bytes(), simd_json::to_vec(self)?)?;
+        Ok(())
+    }
+}
+
+impl SyncAction {
+    /// compare both sides with the last sync
+    pub fn decide(last: &SyncState, remote_etag: Option<&str>, checksum: u32) -> Self {
+        let remote_changed = remote_etag != last.etag.as_deref();
+        let local_changed = Some(checksum) != last.checksum;
+        match (remote_changed, local_changed) {
+            (false, false) => Self::None,
+            (false, true) => Self::Upload,
+            // the remote bundle is removed
+            (true, _) if remote_etag.is_none() => Self::Upload,
+            (true, false) => Self::Download,
+            (true, true) => Self::Conflict,
+        }
+    }
+}
+
+/// a WebDAV client for the sync bundle
+pub struct WebDavClient {
+    url: String,
+    username: Option<String>,
+    password: Option<String>,
+    client: reqwest::Client,
+}
+
+impl WebDavClient {
+    pub fn new(dir: &str, username: Option<String>, password: Option<String>) -> Result<Self> {
+        let url = format!("{}/{SYNC_FILE}", dir.trim_end_matches('/'));
+        let client = reqwest::ClientBuilder::new()
+            .use_rustls_tls()
+            .connect_timeout(CONNECT_TIMEOUT)
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+        Ok(Self {
+            url,
+            username: username.filter(|u| !u.is_empty()),
+            password,
+            client,
+        })
+    }
+
+    /// return `None` if the sync is not configured
+    fn from_config() -> Result<Option<Self>> {
+        let verge = Config::verge();
+        let verge = verge.latest();
+        match verge.webdav_url.as_ref().filter(|u| !u.is_empty()) {
+            Some(url) => Ok(Some(Self::new(
+                url,
+                verge.webdav_username.clone(),
+                verge.webdav_password.clone(),
+            )?)),
+            None => Ok(None),
+        }
+    }
+
+    fn request(&self, method: Method) -> RequestBuilder {
+        let request = self.client.request(method, &self.url);
+        match self.username.as_ref() {
+            Some(username) => request.basic_auth(username, self.password.as_ref()),
+            None => request,
+        }
+    }
+
+    fn get_etag(resp: &reqwest::Response) -> Option<String> {
+        let headers = resp.headers();
+        // fallback to `Last-Modified` if the server does not support `ETag`
+        let value = headers.get(ETAG).or(headers.get(LAST_MODIFIED))?;
+        value.to_str().ok().map(String::from)
+    }
+
+    /// the etag of the remote bundle, `None` if it does not exist
+    pub async fn etag(&self) -> Result<Option<String>> {
+        let resp = self.request(Method::HEAD).send().await?;
+        match resp.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            status if status.is_success() => Ok(Self::get_etag(&resp)),
+            status => bail!("failed to check the WebDAV file with status {status}"),
+        }
+    }
+
+    /// download the remote bundle with its etag
+    pub async fn download(&self) -> Result<(Vec<u8>, Option<String>)> {
+        let resp = self.request(Method::GET).send().await?;
+        let status = resp.status();
+        if !status.is_success() {
+            bail!("failed to download the WebDAV file with status {status}");
+        }
+        let etag = Self::get_etag(&resp);
+        Ok((resp.bytes().await?.to_vec(), etag))
+    }
+
+    /// upload the bundle only if the remote etag is still `etag`,
+    /// return the new etag, or `None` if the remote bundle is changed
+    pub async fn upload(&self, data: Vec<u8>, etag: Option<&str>) -> Result<Option<String>> {
+        let request = match etag {
+            Some(etag) => self.request(Method::PUT).header(IF_MATCH, etag),
+            None => self.request(Method::PUT).header(IF_NONE_MATCH, "*"),
+        };
+        let resp = request.body(data).send().await?;
+        match resp.status() {
+            StatusCode::PRECONDITION_FAILED => Ok(None),
+            status if status.is_success() => match Self::get_etag(&resp) {
+                Some(etag) => Ok(Some(etag)),
+                None => self.etag().await,
+            },
+            status => bail!("failed to upload the WebDAV file with status {status}"),
+        }
+    }
+}
+
+fn exclude_fields(verge: &IVerge) -> Vec<String> {
+    let exclude = verge.webdav_sync_exclude.clone().unwrap_or_default();
+    DEVICE_FIELDS
+        .iter()
+        .map(|f| f.to_string())
+        .chain(exclude)
+        .collect()
+}
+
+/// drop the fields of this device from the settings
+fn strip_verge(verge: &IVerge, exclude: &[String]) -> Result<IVerge> {
+    let mut map = serde_yaml::to_value(verge)?;
+    if let Value::Mapping(map) = &mut map {
+        map.retain(|k, _| !exclude.iter().any(|e| Some(e.as_str()) == k.as_str()));
+    }
+    Ok(serde_yaml::from_value(map)?)
+}
+
+/// apply the remote settings but keep the fields of this device
+fn overlay_verge(local: &IVerge, remote: &IVerge, exclude: &[String]) -> Result<IVerge> {
+    let mut map = serde_yaml::from_value::<Mapping>(serde_yaml::to_value(local)?)?;
+    let remote = serde_yaml::to_value(strip_verge(remote, exclude)?)?;
+    if let Value::Mapping(remote) = remote {
+        for (key, value) in remote {
+            map.insert(key, value);
+        }
+    }
+    Ok(serde_yaml::from_value(Value::Mapping(map))?)
+}
+
+/// the bundle to sync, without the core config and the task storage
+fn sync_bundle(exclude: &[String]) -> Result<BackupBundle> {
+    let mut bundle = BackupBundle::collect(false)?;
+    bundle.verge = strip_verge(&bundle.verge, exclude)?;
+    bundle.clash = Mapping::new();
+    Ok(bundle)
+}
+
+/// the checksum of the data authored by the user, the manifest is not included,
+/// neither are the fields and the files changed by the auto update on every device,
+/// nor the current profile, which is chosen on each device
+fn checksum(bundle: &BackupBundle) -> Result<u32> {
+    let mut profiles = bundle.profiles.clone();
+    profiles.current = None;
+    let mut generated = HashSet::new();
+    for item in profiles.items.iter_mut().flatten() {
+        item.extra = None;
+        item.updated = None;
+        item.etag = None;
+        item.last_modified = None;
+        item.transport = None;
+        item.convert_logs = None;
+        if GENERATED_TYPES.contains(&item.itype.as_deref().unwrap_or_default()) {
+            generated.extend(item.file.clone());
+        }
+    }
+
+    let mut hasher = adler::Adler32::new();
+    hasher.write_slice(serde_yaml::to_string(&bundle.verge)?.as_bytes());
+    hasher.write_slice(serde_yaml::to_string(&profiles)?.as_bytes());
+
+    let mut files = bundle
+        .files
+        .iter()
+        .filter(|(name, _)| !generated.contains(*name))
+        .collect::<Vec<_>>();
+    files.sort_by(|a, b| a.0.cmp(b.0));
+    for (name, data) in files {
+        hasher.write_slice(name.as_bytes());
+        hasher.write_slice(data);
+    }
+    Ok(hasher.checksum())
+}
+
+/// sync with the WebDAV server, `force` resolves the conflict by the given action
+pub async fn sync(force: Option<SyncAction>) -> Result<SyncAction> {
+    let Some(client) = WebDavClient::from_config()? else {
+        bail!("the WebDAV sync is not configured");
+    };
+    let exclude = exclude_fields(&Config::verge().latest());
+    let passphrase = Config::verge().latest().webdav_sync_passphrase.clone();
+    let bundle = sync_bundle(&exclude)?;
+    let local = checksum(&bundle)?;
+    let last = SyncState::load()?;
+    let remote_etag = client.etag().await?;
+
+    let action = force.unwrap_or(SyncAction::decide(&last, remote_etag.as_deref(), local));
+    log::debug!(target: "app", "WebDAV sync action: {action:?}");
+
+    match action {
+        SyncAction::None => {}
+        SyncAction::Upload => {
+            let mut data = Cursor::new(vec![]);
+            bundle.write(&mut data, passphrase.as_deref())?;
+            match client
+                .upload(data.into_inner(), remote_etag.as_deref())
+                .await?
+            {
+                Some(etag) => SyncState {
+                    etag: Some(etag),
+                    checksum: Some(local),
+                }
+                .save()?,
+                // changed by the other device in the meantime
+                None => return Ok(notice_conflict()),
+            }
+        }
+        SyncAction::Download => {
+            let (data, etag) = client.download().await?;
+            let mut remote = BackupBundle::read(Cursor::new(data), passphrase.as_deref())?;
+            remote.verge = overlay_verge(&Config::verge().latest(), &remote.verge, &exclude)?;
+            remote.clash = Config::clash().latest().0.clone();
+            remote.storage = None;
+            // keep the current profile of this device if it's still there
+            let current = Config::profiles().latest().current.clone();
+            if let Some(current) = current.filter(|uid| remote.profiles.get_item(uid).is_ok()) {
+                remote.profiles.current = Some(current);
+            }
+            backup::restore_bundle(remote, RestoreMode::Replace).await?;
+
+            SyncState {
+                etag: etag.or(remote_etag),
+                checksum: Some(checksum(&sync_bundle(&exclude)?)?),
+            }
+            .save()?;
+        }
+        SyncAction::Conflict => {
+            notice_conflict();
+        }
+    }
+    Ok(action)
+}
+
+fn notice_conflict() -> SyncAction {
+    let msg = "both the local and the WebDAV data are changed since the last sync";
+    log::warn!(target: "app", "{msg}");
+    handle::Handle::notice_message("webdav_sync::conflict", msg);
+    SyncAction::Conflict
+}
+
+#[test]
+fn test_sync_action() {
+    let last = SyncState {
+        etag: Some("\"1\"".into()),
+        checksum: Some(1),
+    };
+    assert_eq!(
+        SyncAction::decide(&last, Some("\"1\""), 1),
+        SyncAction::None
+    );
+    assert_eq!(
+        SyncAction::decide(&last, Some("\"1\""), 2),
+        SyncAction::Upload
+    );
+    assert_eq!(
+        SyncAction::decide(&last, Some("\"2\""), 1),
+        SyncAction::Download
+    );
+    assert_eq!(
+        SyncAction::decide(&last, Some("\"2\""), 2),
+        SyncAction::Conflict
+    );
+    assert_eq!(SyncAction::decide(&last, None, 2), SyncAction::Upload);
+
+    // the first sync of this device
+    let first = SyncState::default();
+    assert_eq!(SyncAction::decide(&first, None, 1), SyncAction::Upload);
+    assert_eq!(
+        SyncAction::decide(&first, Some("\"1\""), 1),
+        SyncAction::Conflict
+    );
+}
+
+#[test]
+fn test_sync_checksum() -> Result<()> {
+    use super::backup::{BackupManifest, BACKUP_VERSION};
+    use crate::config::{IProfiles, PrfExtra, PrfItem};
+    use std::collections::HashMap;
+
+    let item = |uid: &str, itype: &str| PrfItem {
+        uid: Some(uid.into()),
+        itype: Some(itype.into()),
+        file: Some(format!("{uid}.yaml")),
+        ..PrfItem::default()
+    };
+    let bundle = |remote: PrfItem, files: [(&str, &str); 2]| BackupBundle {
+        manifest: BackupManifest {
+            version: BACKUP_VERSION,
+            app_version: "1.0.0".into(),
+            created_at: 0,
+            with_storage: false,
+            secrets_salt: None,
+        },
+        verge: IVerge::default(),
+        clash: Mapping::new(),
+        profiles: IProfiles {
+            items: Some(vec![remote, item("m1", "merge")]),
+            ..IProfiles::default()
+        },
+        files: files
+            .into_iter()
+            .map(|(name, data)| (name.to_string(), data.as_bytes().to_vec()))
+            .collect::<HashMap<_, _>>(),
+        storage: None,
+    };
+
+    let local = bundle(
+        item("r1", "remote"),
+        [("r1.yaml", "proxies: []"), ("m1.yaml", "rules: []")],
+    );
+    // the remote profile is updated by the other device
+    let mut updated = item("r1", "remote");
+    updated.updated = Some(1);
+    updated.etag = Some("\"1\"".into());
+    updated.extra = Some(PrfExtra {
+        upload: 1,
+        download: 1,
+        total: 1,
+        expire: 1,
+    });
+    let remote = bundle(
+        updated.clone(),
+        [("r1.yaml", "proxies: [a]"), ("m1.yaml", "rules: []")],
+    );
+    assert_eq!(checksum(&local)?, checksum(&remote)?);
+
+    // the current profile is chosen on each device
+    let mut switched = remote.clone();
+    switched.profiles.current = Some("m1".into());
+    assert_eq!(checksum(&local)?, checksum(&switched)?);
+
+    // the merge is changed by the user
+    let changed = bundle(
+        updated,
+        [("r1.yaml", "proxies: []"), ("m1.yaml", "mode: rule")],
+    );
+    assert_ne!(checksum(&local)?, checksum(&changed)?);
+    Ok(())
+}
+
+/// run the client against a minimal WebDAV stand-in, which only serves one file
+#[test]
+fn test_webdav_client() -> Result<()> {
+    use std::{
+        collections::HashMap,
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+    // (data, version)
+    let file = Arc::new(Mutex::new(None::<(Vec<u8>, u32)>));
+
+    let server_file = file.clone();
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let method = line.split(' ').next().unwrap_or_default().to_string();
+
+            let mut headers = HashMap::new();
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                let Some((key, value)) = header.trim_end().split_once(':') else {
+                    break;
+                };
+                headers.insert(key.trim().to_lowercase(), value.trim().to_string());
+            }
+            let length = headers.get("content-length").and_then(|l| l.parse().ok());
+            let mut body = vec![0; length.unwrap_or(0)];
+            reader.read_exact(&mut body).unwrap();
+
+            let mut file = server_file.lock().unwrap();
+            let etag = file.as_ref().map(|(_, v)| format!("\"{v}\""));
+            let (status, etag, data) = match (method.as_str(), file.as_ref()) {
+                ("HEAD", Some(_)) => ("200 OK", etag, vec![]),
+                ("GET", Some((data, _))) => ("200 OK", etag, data.clone()),
+                ("HEAD" | "GET", None) => ("404 Not Found", None, vec![]),
+                ("PUT", _) => {
+                    let matched = match (headers.get("if-match"), headers.get("if-none-match")) {
+                        (Some(if_match), _) => Some(if_match) == etag.as_ref(),
+                        (None, Some(_)) => etag.is_none(),
+                        (None, None) => true,
+                    };
+                    match matched {
+                        true => {
+                            let version = file.as_ref().map_or(1, |(_, v)| v + 1);
+                            *file = Some((body, version));
+                            ("201 Created", Some(format!("\"{version}\"")), vec![])
+                        }
+                        false => ("412 Precondition Failed", None, vec![]),
+                    }
+                }
+                _ => ("405 Method Not Allowed", None, vec![]),
+            };
+
+            let mut resp = format!(
+                "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n",
+                data.len()
+            );
+            if let Some(etag) = etag {
+                resp.push_str(&format!("ETag: {etag}\r\n"));
+            }
+            resp.push_str("\r\n");
+            let _ = stream.write_all(resp.as_bytes());
+            let _ = stream.write_all(&data);
+        }
+    });
+
+    let client = WebDavClient::new(&format!("http://{addr}/dav/"), None, None)?;
+    tauri::async_runtime::block_on(async {
+        assert_eq!(client.etag().await?, None);
+        let etag = client.upload(b"one".to_vec(), None).await?;
+        assert_eq!(etag.as_deref(), Some("\"1\""));
+        assert_eq!(client.etag().await?, etag);
+
+        // the remote bundle is created by the other device
+        assert_eq!(client.upload(b"two".to_vec(), None).await?, None);
+        let etag = client.upload(b"two".to_vec(), etag.as_deref()).await?;
+        assert_eq!(etag.as_deref(), Some("\"2\""));
+        assert_eq!(client.upload(b"three".to_vec(), Some("\"1\"")).await?, None);
+
+        let (data, remote) = client.download().await?;
+        assert_eq!(data, b"two");
+        assert_eq!(remote, etag);
+        anyhow::Ok(())
+    })?;
+    assert_eq!(file.lock().unwrap().as_ref().map(|f| f.1), Some(2));
+    Ok(())
+}
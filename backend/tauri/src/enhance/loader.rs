@@ -0,0 +1,207 @@
+//! load the script items and the files in `scripts/lib` as ES modules
+use crate::{config::Config, utils::dirs};
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use rquickjs::{
+    loader::{Loader, Resolver},
+    Ctx, Error, Module,
+};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, OnceLock},
+};
+
+const PROFILE_PREFIX: &str = "profile:";
+const LIB_PREFIX: &str = "lib:";
+
+/// the built-in module, which exports the global `verge` object
+const VERGE_MODULE_NAME: &str = "verge";
+const VERGE_MODULE_SOURCE: &str = r#"export default globalThis.verge;
+export const { version, yaml, base64, filterProxies, region, getProfileProxies } = globalThis.verge;"#;
+
+/// whether the script should be run as a module
+pub fn is_module(script: &str) -> bool {
+    static MODULE_SYNTAX: OnceLock<Regex> = OnceLock::new();
+    MODULE_SYNTAX
+        .get_or_init(|| Regex::new(r#"(?m)^\s*(import\s*[\w{*'"]|export\s)"#).unwrap())
+        .is_match(script)
+}
+
+/// the module name of the script item
+pub fn profile_module(uid: &str) -> String {
+    format!("{PROFILE_PREFIX}{uid}")
+}
+
+#[derive(Debug, Default)]
+pub struct ScriptModules {
+    /// the uid, name and file of the script items
+    scripts: Vec<(String, Option<String>, PathBuf)>,
+    lib_dir: PathBuf,
+}
+
+impl ScriptModules {
+    pub fn new(scripts: Vec<(String, Option<String>, PathBuf)>, lib_dir: PathBuf) -> Self {
+        Self { scripts, lib_dir }
+    }
+
+    /// the script items of the profiles and the `scripts/lib` in the app home
+    pub fn load() -> Result<Self> {
+        let profiles_dir = dirs::app_profiles_dir()?;
+        let scripts = Config::profiles()
+            .latest()
+            .get_items()
+            .map(|items| {
+                items
+                    .iter()
+                    .filter(|item| item.itype.as_deref() == Some("script"))
+                    .filter_map(|item| {
+                        Some((
+                            item.uid.clone()?,
+                            item.name.clone(),
+                            profiles_dir.join(item.file.as_ref()?),
+                        ))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let lib_dir = dirs::app_home_dir()?.join("scripts").join("lib");
+        Ok(Self::new(scripts, lib_dir))
+    }
+
+    /// `name` is the built-in `verge`, the uid or the name of a script item,
+    /// or a file in `scripts/lib`, which could be relative to the importing lib file
+    pub fn resolve(&self, base: &str, name: &str) -> Result<String> {
+        if name == VERGE_MODULE_NAME {
+            return Ok(name.to_string());
+        }
+        if !name.starts_with("./") && !name.starts_with("../") {
+            let item = self
+                .scripts
+                .iter()
+                .find(|(uid, ..)| uid == name)
+                .or_else(|| {
+                    self.scripts
+                        .iter()
+                        .find(|(_, n, _)| n.as_deref() == Some(name))
+                });
+            if let Some((uid, ..)) = item {
+                return Ok(profile_module(uid));
+            }
+        }
+
+        // the lib files are relative to the importing lib file, or the lib dir
+        let dir = base
+            .strip_prefix(LIB_PREFIX)
+            .and_then(|path| Path::new(path).parent())
+            .unwrap_or(Path::new(""));
+        let mut path = PathBuf::new();
+        for component in dir.join(name).components() {
+            match component {
+                Component::Normal(c) => path.push(c),
+                Component::CurDir => {}
+                Component::ParentDir if path.pop() => {}
+                _ => bail!("`{name}` is outside of the lib dir"),
+            }
+        }
+        if path.extension().is_none() {
+            path.set_extension("js");
+        }
+        if !self.lib_dir.join(&path).is_file() {
+            bail!("no script profile or lib file is named `{name}`");
+        }
+        Ok(format!(
+            "{LIB_PREFIX}{}",
+            path.to_string_lossy().replace('\\', "/")
+        ))
+    }
+
+    pub fn source(&self, name: &str) -> Result<String> {
+        let path = if name == VERGE_MODULE_NAME {
+            return Ok(VERGE_MODULE_SOURCE.to_string());
+        } else if let Some(uid) = name.strip_prefix(PROFILE_PREFIX) {
+            match self.scripts.iter().find(|(u, ..)| u == uid) {
+                Some((.., path)) => path.clone(),
+                None => bail!("the script profile `{uid}` is not found"),
+            }
+        } else if let Some(path) = name.strip_prefix(LIB_PREFIX) {
+            self.lib_dir.join(path)
+        } else {
+            bail!("unknown module `{name}`");
+        };
+        fs::read_to_string(path).with_context(|| format!("failed to read the module `{name}`"))
+    }
+}
+
+/// resolve the imports and reject the cycles
+pub struct ScriptResolver {
+    modules: Arc<ScriptModules>,
+    /// module -> the imported modules
+    imports: HashMap<String, Vec<String>>,
+}
+
+impl ScriptResolver {
+    pub fn new(modules: Arc<ScriptModules>) -> Self {
+        Self {
+            modules,
+            imports: HashMap::new(),
+        }
+    }
+
+    /// the import path from `from` to `to`
+    fn find_path(&self, from: &str, to: &str) -> Option<Vec<String>> {
+        if from == to {
+            return Some(vec![to.to_string()]);
+        }
+        self.imports.get(from)?.iter().find_map(|next| {
+            let mut path = self.find_path(next, to)?;
+            path.insert(0, from.to_string());
+            Some(path)
+        })
+    }
+}
+
+impl Resolver for ScriptResolver {
+    fn resolve<'js>(
+        &mut self,
+        _ctx: &Ctx<'js>,
+        base: &str,
+        name: &str,
+    ) -> rquickjs::Result<String> {
+        let resolved = self
+            .modules
+            .resolve(base, name)
+            .map_err(|err| Error::new_resolving_message(base, name, format!("{err:#}")))?;
+
+        if let Some(path) = self.find_path(&resolved, base) {
+            let msg = format!("import cycle {} -> {resolved}", path.join(" -> "));
+            return Err(Error::new_resolving_message(base, name, msg));
+        }
+        self.imports
+            .entry(base.to_string())
+            .or_default()
+            .push(resolved.clone());
+        Ok(resolved)
+    }
+}
+
+pub struct ScriptLoader {
+    modules: Arc<ScriptModules>,
+}
+
+impl ScriptLoader {
+    pub fn new(modules: Arc<ScriptModules>) -> Self {
+        Self { modules }
+    }
+}
+
+impl Loader for ScriptLoader {
+    fn load<'js>(&mut self, ctx: &Ctx<'js>, name: &str) -> rquickjs::Result<Module<'js>> {
+        let source = self
+            .modules
+            .source(name)
+            .map_err(|err| Error::new_loading_message(name, format!("{err:#}")))?;
+        Module::declare(ctx.clone(), name, source)
+    }
+}
@@ -1,64 +1,191 @@
-use super::{use_filter, use_lowercase};
+use super::use_lowercase;
+use regex::Regex;
 use serde_yaml::{Mapping, Sequence, Value};
 use tracing_attributes::instrument;
 
-const MERGE_FIELDS: [&str; 6] = [
-    "prepend-rules",
-    "append-rules",
-    "prepend-proxies",
-    "append-proxies",
-    "prepend-proxy-groups",
-    "append-proxy-groups",
-];
+/// merge the nested mappings instead of overriding the top-level keys
+const DEEP_MERGE_KEY: &str = "deep-merge";
+
+/// the lists which `prepend-` and `append-` work with, without the marker
+const LIST_FIELDS: [&str; 3] = ["rules", "proxies", "proxy-groups"];
+
+/// the operator of a merge key, the markers of the top-level keys always work,
+/// the nested ones only work in the deep merge
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MergeOp<'a> {
+    /// `key`, override or merge deeply
+    Merge(&'a str),
+    /// `key=`, override even in the deep merge
+    Replace(&'a str),
+    /// `key!`, remove the key
+    Delete(&'a str),
+    /// `key(prepend)`, prepend the items to the sequence
+    Prepend(&'a str),
+    /// `key(append)`, append the items to the sequence
+    Append(&'a str),
+    /// `key(filter)`, keep the items matching the patterns
+    Filter(&'a str),
+    /// `key(exclude)`, remove the items matching the patterns
+    Exclude(&'a str),
+}
+
+impl<'a> MergeOp<'a> {
+    /// the operators are marked explicitly, so the keys like `tun.exclude-interface` are kept
+    fn parse(key: &'a str, top_level: bool) -> Self {
+        if top_level {
+            // `prepend-rules` and the like, which are supported before the markers
+            for field in LIST_FIELDS {
+                if key.strip_prefix("prepend-") == Some(field) {
+                    return Self::Prepend(field);
+                }
+                if key.strip_prefix("append-") == Some(field) {
+                    return Self::Append(field);
+                }
+            }
+        }
+        let suffixed = [
+            ("(prepend)", Self::Prepend as fn(&'a str) -> Self),
+            ("(append)", Self::Append),
+            ("(filter)", Self::Filter),
+            ("(exclude)", Self::Exclude),
+            ("!", Self::Delete),
+            ("=", Self::Replace),
+        ];
+        for (suffix, op) in suffixed {
+            if let Some(name) = key.strip_suffix(suffix).filter(|n| !n.is_empty()) {
+                return op(name);
+            }
+        }
+        Self::Merge(key)
+    }
+
+    /// the list operators run after the others, so `prepend-rules` works with `rules`
+    fn is_list_op(&self) -> bool {
+        matches!(
+            self,
+            Self::Prepend(_) | Self::Append(_) | Self::Filter(_) | Self::Exclude(_)
+        )
+    }
+}
 
 #[instrument(skip(merge, config))]
 pub fn use_merge(merge: Mapping, mut config: Mapping) -> Mapping {
     tracing::trace!("original config: {:#?}", config);
     tracing::trace!("merge: {:#?}", merge);
-    // 直接覆盖原字段
-    use_lowercase(merge.clone())
-        .into_iter()
-        .filter(|(key, _)| !MERGE_FIELDS.contains(&key.as_str().unwrap_or_default()))
-        .for_each(|(key, value)| {
-            tracing::debug!("override: key: {:?}, value: {:?}", key, value);
+    let mut merge = use_lowercase(merge);
+    let deep = merge
+        .remove(DEEP_MERGE_KEY)
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    merge_mapping(&mut config, merge, deep, true);
+
+    // the lists are always kept
+    LIST_FIELDS.iter().for_each(|key| {
+        if !config.get(key).is_some_and(Value::is_sequence) {
+            config.insert(Value::from(*key), Value::from(Sequence::new()));
+        }
+    });
+    tracing::trace!("merged config: {:#?}", config);
+    config
+}
+
+/// merge the `patch` into the `config` by the operators of the keys
+fn merge_mapping(config: &mut Mapping, patch: Mapping, deep: bool, top_level: bool) {
+    let (list_ops, ops): (Vec<_>, Vec<_>) = patch.into_iter().partition(|(key, _)| {
+        key.as_str()
+            .is_some_and(|key| MergeOp::parse(key, top_level).is_list_op())
+    });
+
+    for (key, value) in ops.into_iter().chain(list_ops) {
+        let Some(key_str) = key.as_str() else {
             config.insert(key, value);
-        });
-
-    let merge_list = MERGE_FIELDS.iter().map(|s| s.to_string());
-    let merge_list = merge_list.collect::<Vec<String>>();
-    let merge = use_filter(merge, &merge_list, true);
-
-    ["rules", "proxies", "proxy-groups"]
-        .iter()
-        .for_each(|key_str| {
-            let key_val = Value::from(key_str.to_string());
-
-            let mut list = Sequence::default();
-            list = config.get(&key_val).map_or(list.clone(), |val| {
-                val.as_sequence().map_or(list, |v| v.clone())
-            });
-
-            let pre_key = Value::from(format!("prepend-{key_str}"));
-            let post_key = Value::from(format!("append-{key_str}"));
-
-            if let Some(pre_val) = merge.get(&pre_key) {
-                if pre_val.is_sequence() {
-                    let mut pre_val = pre_val.as_sequence().unwrap().clone();
-                    pre_val.extend(list);
-                    list = pre_val;
+            continue;
+        };
+        match MergeOp::parse(key_str, top_level) {
+            MergeOp::Merge(name) => match (config.get_mut(name), value) {
+                (Some(Value::Mapping(old)), Value::Mapping(new)) if deep => {
+                    merge_mapping(old, new, deep, false);
                 }
+                (_, value) => {
+                    tracing::debug!("override: key: {:?}, value: {:?}", name, value);
+                    config.insert(name.into(), resolve(value, deep));
+                }
+            },
+            MergeOp::Replace(name) => {
+                config.insert(name.into(), resolve(value, deep));
             }
-
-            if let Some(post_val) = merge.get(&post_key) {
-                if post_val.is_sequence() {
-                    list.extend(post_val.as_sequence().unwrap().clone());
+            MergeOp::Delete(name) => {
+                config.remove(name);
+            }
+            op @ (MergeOp::Prepend(name) | MergeOp::Append(name)) => {
+                let Value::Sequence(items) = resolve(value, deep) else {
+                    tracing::warn!("the value of `{key_str}` should be a sequence");
+                    continue;
+                };
+                let list = config
+                    .entry(name.into())
+                    .or_insert_with(|| Value::from(Sequence::new()));
+                if !list.is_sequence() {
+                    *list = Value::from(Sequence::new());
+                }
+                let list = list.as_sequence_mut().unwrap();
+                if let MergeOp::Prepend(_) = op {
+                    list.splice(0..0, items);
+                } else {
+                    list.extend(items);
                 }
             }
+            op @ (MergeOp::Filter(name) | MergeOp::Exclude(name)) => {
+                let keep = matches!(op, MergeOp::Filter(_));
+                let patterns = match compile_patterns(&value) {
+                    Ok(patterns) => patterns,
+                    Err(err) => {
+                        tracing::warn!("invalid pattern of `{key_str}`: {err}");
+                        continue;
+                    }
+                };
+                if let Some(Value::Sequence(list)) = config.get_mut(name) {
+                    list.retain(|item| {
+                        let matched = item_text(item)
+                            .is_some_and(|text| patterns.iter().any(|p| p.is_match(text)));
+                        matched == keep
+                    });
+                }
+            }
+        }
+    }
+}
 
-            config.insert(key_val, Value::from(list));
-        });
-    tracing::trace!("merged config: {:#?}", config);
-    config
+/// resolve the operators in the nested mappings of the deep merge
+fn resolve(value: Value, deep: bool) -> Value {
+    match value {
+        Value::Mapping(patch) if deep => {
+            let mut mapping = Mapping::new();
+            merge_mapping(&mut mapping, patch, deep, false);
+            Value::Mapping(mapping)
+        }
+        value => value,
+    }
+}
+
+/// a pattern or a sequence of patterns
+fn compile_patterns(value: &Value) -> Result<Vec<Regex>, regex::Error> {
+    match value {
+        Value::Sequence(seq) => seq
+            .iter()
+            .filter_map(Value::as_str)
+            .map(Regex::new)
+            .collect(),
+        value => value.as_str().map(Regex::new).into_iter().collect(),
+    }
+}
+
+/// the name of the proxy or the group, or the string item, e.g. the rule
+fn item_text(item: &Value) -> Option<&str> {
+    match item {
+        Value::Mapping(mapping) => mapping.get("name").and_then(Value::as_str),
+        item => item.as_str(),
+    }
 }
 
 #[test]
@@ -98,3 +225,105 @@ fn test_merge() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_deep_merge() -> anyhow::Result<()> {
+    let config = r"
+    dns:
+      enable: false
+      nameserver: [1.1.1.1, 8.8.8.8]
+      fake-ip-filter: ['*.lan']
+      nameserver-policy:
+        '+.internal.com': 10.0.0.1
+    tun:
+      enable: false
+      stack: system
+    rules:
+      - DOMAIN,a.com,DIRECT
+      - DOMAIN,ads.com,REJECT
+    proxies:
+      - name: HK 01
+      - name: US 01
+    hosts:
+      a.com: 1.2.3.4
+  ";
+    let config = serde_yaml::from_str::<Mapping>(config)?;
+
+    // the nested mappings are overridden by default
+    let merge = serde_yaml::from_str::<Mapping>("dns:\n  enable: true")?;
+    let result = use_merge(merge, config.clone());
+    assert_eq!(
+        result["dns"],
+        serde_yaml::from_str::<Value>("enable: true")?
+    );
+
+    let merge = r"
+    deep-merge: true
+    dns:
+      enable: true
+      nameserver(prepend): [223.5.5.5]
+      nameserver(exclude): ^8\.
+      fake-ip-filter(append): ['+.local']
+      nameserver-policy:
+        '+.corp.com': 10.0.0.2
+    tun=:
+      enable: true
+      exclude-interface: [wlan0]
+    hosts!: null
+    rules(exclude): ',REJECT$'
+    proxies(filter): [HK]
+    prepend-rules: ['MATCH,DIRECT']
+    proxy-providers:
+      香港节点:
+        exclude-filter: 过期
+  ";
+    let merge = serde_yaml::from_str::<Mapping>(merge)?;
+    let result = use_merge(merge, config);
+
+    let expected = r"
+    dns:
+      enable: true
+      nameserver: [223.5.5.5, 1.1.1.1]
+      fake-ip-filter: ['*.lan', '+.local']
+      nameserver-policy:
+        '+.internal.com': 10.0.0.1
+        '+.corp.com': 10.0.0.2
+    tun:
+      enable: true
+      exclude-interface: [wlan0]
+    rules:
+      - MATCH,DIRECT
+      - DOMAIN,a.com,DIRECT
+    proxies:
+      - name: HK 01
+    proxy-groups: []
+    proxy-providers:
+      香港节点:
+        exclude-filter: 过期
+  ";
+    assert_eq!(result, serde_yaml::from_str::<Mapping>(expected)?);
+
+    // the nested keys like the operators are kept in the deep merge
+    let config = r"
+    tun:
+      enable: true
+      exclude-interface: [eth1]
+  ";
+    let merge = r"
+    deep-merge: true
+    tun:
+      stack: gvisor
+      exclude-interface: [wlan0]
+      exclude-uid: [1000]
+  ";
+    let merge = serde_yaml::from_str::<Mapping>(merge)?;
+    let result = use_merge(merge, serde_yaml::from_str::<Mapping>(config)?);
+    let expected = r"
+    enable: true
+    exclude-interface: [wlan0]
+    stack: gvisor
+    exclude-uid: [1000]
+  ";
+    assert_eq!(result["tun"], serde_yaml::from_str::<Value>(expected)?);
+    Ok(())
+}
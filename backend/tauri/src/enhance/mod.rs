@@ -1,12 +1,16 @@
 mod chain;
 mod field;
+mod loader;
 mod merge;
 mod script;
+mod trace;
 mod tun;
+mod validate;
 
 use self::field::*;
 
-use self::{chain::*, merge::*, script::*, tun::*};
+use self::{chain::*, merge::*, script::*, trace::StageKind, tun::*, validate::*};
+pub use self::{script::check_script, trace::EnhanceTrace, validate::ValidationError};
 use crate::config::Config;
 use serde_yaml::Mapping;
 use std::collections::{HashMap, HashSet};
@@ -14,12 +18,44 @@ use std::collections::{HashMap, HashSet};
 type ResultLog = Vec<(String, String)>;
 
 /// Enhance mode
-/// 返回最终配置、该配置包含的键、和script执行的结果
-pub fn enhance() -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
+/// 返回最终配置、该配置包含的键、script执行的结果和配置检查的错误
+pub fn enhance() -> (
+    Mapping,
+    Vec<String>,
+    HashMap<String, ResultLog>,
+    Vec<ValidationError>,
+) {
+    run_enhance(None)
+}
+
+/// 试运行，记录每一步之后的配置，不会影响运行中的核心
+pub fn dry_run() -> EnhanceTrace {
+    let mut trace = EnhanceTrace::default();
+    let (config, _, logs, errors) = run_enhance(Some(&mut trace));
+    trace.config = config;
+    trace.logs = logs;
+    trace.errors = errors;
+    trace
+}
+
+fn run_enhance(
+    mut trace: Option<&mut EnhanceTrace>,
+) -> (
+    Mapping,
+    Vec<String>,
+    HashMap<String, ResultLog>,
+    Vec<ValidationError>,
+) {
+    let mut record = |kind: StageKind, uid: Option<&str>, config: &Mapping| {
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.record(kind, uid, config);
+        }
+    };
+
     // config.yaml 的配置
     let clash_config = { Config::clash().latest().0.clone() };
 
-    let (clash_core, enable_tun, enable_builtin, enable_filter) = {
+    let (clash_core, enable_tun, enable_builtin, enable_filter, limits) = {
         let verge = Config::verge();
         let verge = verge.latest();
         (
@@ -27,54 +63,70 @@ pub fn enhance() -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
             verge.enable_tun_mode.unwrap_or(false),
             verge.enable_builtin_enhanced.unwrap_or(true),
             verge.enable_clash_fields.unwrap_or(true),
+            ScriptLimits {
+                timeout: verge.script_timeout.unwrap_or(DEFAULT_SCRIPT_TIMEOUT),
+                memory: verge
+                    .script_memory_limit
+                    .unwrap_or(DEFAULT_SCRIPT_MEMORY_LIMIT),
+            },
         )
     };
 
     // 从profiles里拿东西
-    let (mut config, chain, valid) = {
+    let (mut config, current_uid, chain, valid) = {
         let profiles = Config::profiles();
         let profiles = profiles.latest();
 
         let current = profiles.current_mapping().unwrap_or_default();
+        let current_uid = profiles.get_current().unwrap_or_default();
 
-        let chain = match profiles.chain.as_ref() {
-            Some(chain) => chain
-                .iter()
-                .filter_map(|uid| profiles.get_item(uid).ok())
-                .filter_map(<Option<ChainItem>>::from)
-                .collect::<Vec<ChainItem>>(),
-            None => vec![],
-        };
+        // the global chain and the chain of the current profile
+        let chain = profiles
+            .active_chain()
+            .iter()
+            .filter_map(|uid| profiles.get_item(uid).ok())
+            .filter_map(<Option<ChainItem>>::from)
+            .collect::<Vec<ChainItem>>();
 
         let valid = profiles.valid.clone().unwrap_or_default();
 
-        (current, chain, valid)
+        (current, current_uid, chain, valid)
     };
 
     let mut result_map = HashMap::new(); // 保存脚本日志
     let mut exists_keys = use_keys(&config); // 保存出现过的keys
+    let mut sources = ConfigSources::default(); // 记录配置项的来源
+    sources.track(&current_uid, None, &config);
+    record(StageKind::Profile, Some(current_uid.as_str()), &config);
 
     let valid = use_valid_fields(valid);
     config = use_filter(config, &valid, enable_filter);
+    record(StageKind::Filter, None, &config);
 
     // 处理用户的profile
     chain.into_iter().for_each(|item| match item.data {
         ChainType::Merge(merge) => {
             exists_keys.extend(use_keys(&merge));
-            config = use_merge(merge, config.to_owned());
-            config = use_filter(config.to_owned(), &valid, enable_filter);
+            let res_config = use_merge(merge, config.to_owned());
+            let res_config = use_filter(res_config, &valid, enable_filter);
+            sources.track(&item.uid, Some(&config), &res_config);
+            config = res_config;
+            record(StageKind::Merge, Some(item.uid.as_str()), &config);
         }
         ChainType::Script(script) => {
             let mut logs = vec![];
 
-            match use_script(script, config.to_owned()) {
+            match use_script(&item.uid, script, config.to_owned(), limits) {
                 Ok((res_config, res_logs)) => {
                     exists_keys.extend(use_keys(&res_config));
-                    config = use_filter(res_config, &valid, enable_filter);
+                    let res_config = use_filter(res_config, &valid, enable_filter);
+                    sources.track(&item.uid, Some(&config), &res_config);
+                    config = res_config;
                     logs.extend(res_logs);
                 }
                 Err(err) => logs.push(("exception".into(), err.to_string())),
             }
+            record(StageKind::Script, Some(item.uid.as_str()), &config);
 
             result_map.insert(item.uid, logs);
         }
@@ -88,6 +140,7 @@ pub fn enhance() -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
         .for_each(|(key, value)| {
             config.insert(key.to_owned(), value.clone());
         });
+    record(StageKind::HandleFields, None, &config);
 
     let clash_fields = use_clash_fields();
 
@@ -101,25 +154,36 @@ pub fn enhance() -> (Mapping, Vec<String>, HashMap<String, ResultLog>) {
                 log::debug!(target: "app", "run builtin script {}", item.uid);
 
                 if let ChainType::Script(script) = item.data {
-                    match use_script(script, config.to_owned()) {
+                    match use_script(&item.uid, script, config.to_owned(), limits) {
                         Ok((res_config, _)) => {
-                            config = use_filter(res_config, &clash_fields, enable_filter);
+                            let res_config = use_filter(res_config, &clash_fields, enable_filter);
+                            sources.track(&item.uid, Some(&config), &res_config);
+                            config = res_config;
                         }
                         Err(err) => {
                             log::error!(target: "app", "builtin script error `{err}`");
                         }
                     }
+                    record(StageKind::Builtin, Some(item.uid.as_str()), &config);
                 }
             });
     }
 
     config = use_filter(config, &clash_fields, enable_filter);
+    record(StageKind::Filter, None, &config);
     config = use_tun(config, enable_tun);
+    record(StageKind::Tun, None, &config);
     config = use_sort(config, enable_filter);
+    record(StageKind::Sort, None, &config);
 
     let mut exists_set = HashSet::new();
     exists_set.extend(exists_keys.into_iter().filter(|s| clash_fields.contains(s)));
     exists_keys = exists_set.into_iter().collect();
 
-    (config, exists_keys, result_map)
+    let errors = use_validate(&config, &sources);
+    for err in errors.iter() {
+        log::warn!(target: "app", "invalid config: {}", err.message);
+    }
+
+    (config, exists_keys, result_map, errors)
 }
@@ -1,13 +1,210 @@
-use super::use_lowercase;
-use anyhow::Result;
-use serde_yaml::Mapping;
+use super::{
+    loader::{is_module, profile_module, ScriptLoader, ScriptModules, ScriptResolver},
+    use_lowercase,
+};
+use crate::{
+    config::{convert, Config},
+    utils::{dirs, help},
+};
+use anyhow::{anyhow, bail, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use regex::Regex;
+use serde_json::{json, Value as JsonValue};
+use serde_yaml::{Mapping, Value};
+use std::{
+    path::Path,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
 
-pub fn use_script(script: String, config: Mapping) -> Result<(Mapping, Vec<(String, String)>)> {
-    use rquickjs::{function::Func, Context, Runtime};
-    use std::sync::{Arc, Mutex};
+pub const DEFAULT_SCRIPT_TIMEOUT: u64 = 5000;
+pub const DEFAULT_SCRIPT_MEMORY_LIMIT: u64 = 128;
 
-    let runtime = Runtime::new().unwrap();
-    let context = Context::full(&runtime).unwrap();
+/// the limits of a script run, 0 to disable
+#[derive(Debug, Clone, Copy)]
+pub struct ScriptLimits {
+    /// in milliseconds
+    pub timeout: u64,
+    /// in MB
+    pub memory: u64,
+}
+
+impl Default for ScriptLimits {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_SCRIPT_TIMEOUT,
+            memory: DEFAULT_SCRIPT_MEMORY_LIMIT,
+        }
+    }
+}
+
+/// the built-in `verge` module of the scripts, bump the version when the api changes
+const VERGE_MODULE: &str = r#"var verge = (() => {
+  const call = (op, data) => {
+    const res = JSON.parse(__verge_host__(op, data));
+    if ("err" in res) throw new Error(res.err);
+    return res.ok;
+  };
+  return Object.freeze({
+    version: 1,
+    yaml: Object.freeze({
+      parse: (text) => call("yaml_parse", String(text)),
+      stringify: (data) => call("yaml_stringify", JSON.stringify(data)),
+    }),
+    base64: Object.freeze({
+      encode: (text) => call("base64_encode", String(text)),
+      decode: (text) => call("base64_decode", String(text)),
+    }),
+    // keep the proxies whose name matches the pattern
+    filterProxies(proxies, pattern, flags) {
+      const regex = pattern instanceof RegExp ? pattern : new RegExp(pattern, flags);
+      return (proxies || []).filter((p) => {
+        regex.lastIndex = 0;
+        return regex.test(p && p.name);
+      });
+    },
+    // the flag emoji of the region, or null
+    region: (name) => call("region", String(name)),
+    getProfileProxies: (uid) => call("profile_proxies", String(uid)),
+  });
+})();"#;
+
+/// the host functions of the `verge` module, the result is wrapped as `{ok}` or `{err}`
+fn call_host(op: &str, data: &str) -> String {
+    let res = (|| -> Result<JsonValue> {
+        Ok(match op {
+            "yaml_parse" => serde_yaml::from_str::<JsonValue>(data)?,
+            "yaml_stringify" => {
+                let value = serde_json::from_str::<Value>(data)?;
+                serde_yaml::to_string(&value)?.into()
+            }
+            "base64_encode" => STANDARD.encode(data).into(),
+            "base64_decode" => convert::decode_base64(data)
+                .ok_or(anyhow!("invalid base64 string"))?
+                .into(),
+            "region" => convert::region_flag(data).into(),
+            "profile_proxies" => profile_proxies(data)?,
+            _ => bail!("unknown host function `{op}`"),
+        })
+    })();
+    match res {
+        Ok(value) => json!({ "ok": value }),
+        Err(err) => json!({ "err": format!("{err:#}") }),
+    }
+    .to_string()
+}
+
+/// the proxies of another profile, the profiles are not locked when reading the file
+fn profile_proxies(uid: &str) -> Result<JsonValue> {
+    let file = {
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+        profiles.get_item(&uid.to_string())?.file.clone()
+    };
+    read_proxies(uid, file, &dirs::app_profiles_dir()?)
+}
+
+fn read_proxies(uid: &str, file: Option<String>, profiles_dir: &Path) -> Result<JsonValue> {
+    let Some(file) = file else {
+        bail!("the profile `{uid}` has no file");
+    };
+    let profile = help::read_merge_mapping(&profiles_dir.join(file))?;
+    let proxies = profile
+        .get("proxies")
+        .cloned()
+        .unwrap_or(Value::Sequence(vec![]));
+    Ok(serde_json::to_value(proxies)?)
+}
+
+/// compile the script without running it, e.g. the downloaded one
+pub fn check_script(script: &str) -> Result<()> {
+    use rquickjs::{CatchResultExt, Context, Module, Runtime};
+
+    static MAIN_FN: OnceLock<Regex> = OnceLock::new();
+
+    let runtime = Runtime::new()?;
+    let context = Context::full(&runtime)?;
+    context.with(|ctx| -> Result<()> {
+        if is_module(script) {
+            Module::declare(ctx.clone(), "check", script)
+                .catch(&ctx)
+                .map_err(|err| anyhow!("{err}"))?;
+        } else {
+            // the script is parsed as the body of a function, which is not called
+            let source = format!("new Function({})", serde_json::to_string(script)?);
+            ctx.eval::<(), _>(source)
+                .catch(&ctx)
+                .map_err(|err| anyhow!("{err}"))?;
+        }
+        Ok(())
+    })?;
+
+    let main = MAIN_FN.get_or_init(|| {
+        Regex::new(r"(\bfunction\s*\*?\s*main\s*\(|\b(var|let|const)\s+main\s*=)").unwrap()
+    });
+    if !main.is_match(script) {
+        bail!("the script does not define the `main` function");
+    }
+    Ok(())
+}
+
+/// run the script of the item `uid`, the script which imports or exports runs as a module
+pub fn use_script(
+    uid: &str,
+    script: String,
+    config: Mapping,
+    limits: ScriptLimits,
+) -> Result<(Mapping, Vec<(String, String)>)> {
+    let modules = match is_module(&script) {
+        true => match ScriptModules::load() {
+            Ok(modules) => Some(modules),
+            Err(err) => return Ok((config, vec![("exception".into(), format!("{err:#}"))])),
+        },
+        false => None,
+    };
+    run_script(uid, script, config, limits, modules)
+}
+
+fn run_script(
+    uid: &str,
+    script: String,
+    config: Mapping,
+    limits: ScriptLimits,
+    modules: Option<ScriptModules>,
+) -> Result<(Mapping, Vec<(String, String)>)> {
+    use rquickjs::{function::Func, CatchResultExt, Context, Module, Runtime};
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    };
+
+    let runtime = Runtime::new()?;
+    let as_module = modules.is_some();
+    if let Some(modules) = modules {
+        let modules = Arc::new(modules);
+        runtime.set_loader(
+            ScriptResolver::new(modules.clone()),
+            ScriptLoader::new(modules),
+        );
+    }
+    if limits.memory > 0 {
+        let bytes = limits.memory.saturating_mul(1024 * 1024);
+        runtime.set_memory_limit(usize::try_from(bytes).unwrap_or(usize::MAX));
+    }
+    let timed_out = Arc::new(AtomicBool::new(false));
+    if limits.timeout > 0 {
+        let deadline = Instant::now() + Duration::from_millis(limits.timeout);
+        let timed_out = timed_out.clone();
+        // 返回 true 时中断脚本，这个异常无法被脚本 catch
+        runtime.set_interrupt_handler(Some(Box::new(move || {
+            let expired = Instant::now() >= deadline;
+            if expired {
+                timed_out.store(true, Ordering::Relaxed);
+            }
+            expired
+        })));
+    }
+    let context = Context::full(&runtime)?;
     let outputs = Arc::new(Mutex::new(vec![]));
 
     let copy_outputs = outputs.clone();
@@ -29,13 +226,32 @@ pub fn use_script(script: String, config: Mapping) -> Result<(Mapping, Vec<(Stri
       });"#,
         )?;
 
+        ctx.globals().set(
+            "__verge_host__",
+            Func::from(|op: String, data: String| call_host(&op, &data)),
+        )?;
+        ctx.eval::<(), _>(VERGE_MODULE)?;
+
+        // the functions of a module are not global, so expose the `main` of it
+        let (script, main) = if as_module {
+            let source = format!(
+                "{script}\n;globalThis.__verge_main__ = typeof main === 'function' ? main : undefined;"
+            );
+            Module::evaluate(ctx.clone(), profile_module(uid), source)
+                .catch(&ctx)
+                .map_err(|err| anyhow!("{err}"))?;
+            (String::new(), "__verge_main__")
+        } else {
+            (script, "main")
+        };
+
         let config = use_lowercase(config.clone());
         let config_str = serde_json::to_string(&config)?;
 
         let code = format!(
             r#"try{{
         {script};
-        JSON.stringify(main({config_str})||'')
+        JSON.stringify({main}({config_str})||'')
       }} catch(err) {{
         `__error_flag__ ${{err.toString()}}`
       }}"#
@@ -54,7 +270,14 @@ pub fn use_script(script: String, config: Mapping) -> Result<(Mapping, Vec<(Stri
     match result {
         Ok(config) => Ok((use_lowercase(config), out.to_vec())),
         Err(err) => {
-            out.push(("exception".into(), err.to_string()));
+            let msg = if timed_out.load(Ordering::Relaxed) {
+                format!("the script is timed out after {}ms", limits.timeout)
+            } else if err.to_string().contains("out of memory") {
+                format!("the script exceeds the memory limit of {}MB", limits.memory)
+            } else {
+                err.to_string()
+            };
+            out.push(("exception".into(), msg));
             Ok((config, out.to_vec()))
         }
     }
@@ -84,7 +307,8 @@ fn test_script() {
   "#;
 
     let config = serde_yaml::from_str(config).unwrap();
-    let (config, results) = use_script(script.into(), config).unwrap();
+    let (config, results) =
+        use_script("test", script.into(), config, ScriptLimits::default()).unwrap();
 
     let config_str = serde_yaml::to_string(&config).unwrap();
 
@@ -92,3 +316,184 @@ fn test_script() {
 
     dbg!(results);
 }
+
+#[test]
+fn test_script_profile_proxies() {
+    let dir = tempfile::tempdir().unwrap();
+    let write = |file: &str, content: &str| std::fs::write(dir.path().join(file), content).unwrap();
+    write(
+        "r1.yaml",
+        "proxies:\n  - { name: HK 01, type: ss }\nrules: []",
+    );
+    write("r2.yaml", "rules: []");
+
+    let proxies = read_proxies("r1", Some("r1.yaml".into()), dir.path()).unwrap();
+    assert_eq!(proxies, json!([{ "name": "HK 01", "type": "ss" }]));
+    let proxies = read_proxies("r2", Some("r2.yaml".into()), dir.path()).unwrap();
+    assert_eq!(proxies, json!([]));
+    let err = read_proxies("r3", None, dir.path()).unwrap_err();
+    assert_eq!(err.to_string(), "the profile `r3` has no file");
+    assert!(read_proxies("r4", Some("r4.yaml".into()), dir.path()).is_err());
+}
+
+#[test]
+fn test_script_module() {
+    let script = r#"
+    function main(config) {
+      const proxies = verge.filterProxies(config.proxies, "hk|japan", "i");
+      return {
+        version: verge.version,
+        names: proxies.map((p) => p.name),
+        regions: proxies.map((p) => verge.region(p.name)),
+        unknown: verge.region("somewhere"),
+        yaml: verge.yaml.stringify(verge.yaml.parse("a: 1")),
+        base64: verge.base64.decode(verge.base64.encode("ss://节点")),
+        error: (() => { try { verge.base64.decode("!!!") } catch (err) { return err.message } })(),
+      };
+    }
+  "#;
+
+    let config = r#"
+    proxies:
+      - name: HK 01
+      - name: US 01
+      - name: Japan 01
+  "#;
+
+    let config = serde_yaml::from_str(config).unwrap();
+    let (config, logs) =
+        use_script("test", script.into(), config, ScriptLimits::default()).unwrap();
+    assert!(logs.is_empty(), "{logs:?}");
+
+    let expected = r#"
+    version: 1
+    names: [HK 01, Japan 01]
+    regions: ["🇭🇰", "🇯🇵"]
+    unknown: null
+    yaml: "a: 1\n"
+    base64: "ss://节点"
+    error: invalid base64 string
+  "#;
+    assert_eq!(config, serde_yaml::from_str::<Mapping>(expected).unwrap());
+}
+
+#[test]
+fn test_script_imports() {
+    use std::fs;
+
+    let dir = tempfile::tempdir().unwrap();
+    let lib = dir.path().join("lib");
+    fs::create_dir_all(lib.join("net")).unwrap();
+    let write = |path: &str, content: &str| fs::write(lib.join(path), content).unwrap();
+    write("prefix.js", r#"export const prefix = "flag-";"#);
+    write(
+        "net/flag.js",
+        r#"import { prefix } from "../prefix.js"; export const flag = (name) => prefix + name;"#,
+    );
+    write("a.js", r#"import { b } from "./b"; export const a = 1;"#);
+    write("b.js", r#"import { a } from "./a"; export const b = 2;"#);
+    let shared = dir.path().join("shared.js");
+    fs::write(
+        &shared,
+        "export function rename(config) { config.renamed = true; return config; }",
+    )
+    .unwrap();
+    let modules = || {
+        let scripts = vec![("shared".into(), Some("Shared".into()), shared.clone())];
+        Some(ScriptModules::new(scripts, lib.clone()))
+    };
+    let run = |script: &str| {
+        let limits = ScriptLimits::default();
+        run_script("main", script.into(), Mapping::new(), limits, modules()).unwrap()
+    };
+
+    let (config, logs) = run(r#"
+    import verge, { version } from "verge";
+    import { rename } from "Shared";
+    import { flag } from "net/flag";
+    export function main(config) {
+      config.name = flag("HK");
+      config.version = version;
+      config.yaml = verge.yaml.stringify({ a: 1 });
+      return rename(config);
+    }"#);
+    assert!(logs.is_empty(), "{logs:?}");
+    assert_eq!(
+        config,
+        serde_yaml::from_str::<Mapping>(
+            "name: flag-HK\nversion: 1\nyaml: \"a: 1\\n\"\nrenamed: true"
+        )
+        .unwrap()
+    );
+
+    let (_, logs) = run(r#"import { x } from "missing";
+    function main(config) { return config; }"#);
+    assert_eq!(logs[0].0, "exception");
+    assert!(
+        logs[0]
+            .1
+            .contains("no script profile or lib file is named `missing`"),
+        "{logs:?}"
+    );
+
+    let (_, logs) = run(r#"import { a } from "a";
+    function main(config) { return config; }"#);
+    assert!(
+        logs[0]
+            .1
+            .contains("import cycle lib:a.js -> lib:b.js -> lib:a.js"),
+        "{logs:?}"
+    );
+}
+
+#[test]
+fn test_script_limits() {
+    let config = serde_yaml::from_str::<Mapping>("rules: []").unwrap();
+    let limits = ScriptLimits {
+        timeout: 200,
+        memory: 16,
+    };
+
+    let script = "function main(config) { while (true) {} }";
+    let (res, logs) = use_script("test", script.into(), config.clone(), limits).unwrap();
+    assert_eq!(res, config);
+    assert_eq!(
+        logs,
+        [(
+            "exception".to_string(),
+            "the script is timed out after 200ms".to_string()
+        )]
+    );
+
+    let script = r#"function main(config) {
+      const list = [];
+      while (true) { list.push("x".repeat(1024 * 1024)); }
+    }"#;
+    let (res, logs) = use_script("test", script.into(), config.clone(), limits).unwrap();
+    assert_eq!(res, config);
+    assert_eq!(logs[0].0, "exception");
+
+    // the huge limit is saturated
+    let limits = ScriptLimits {
+        timeout: 0,
+        memory: u64::MAX,
+    };
+    let script = "function main(config) { return config; }";
+    let (res, logs) = use_script("test", script.into(), config.clone(), limits).unwrap();
+    assert_eq!(res, config);
+    assert!(logs.is_empty(), "{logs:?}");
+}
+
+#[test]
+fn test_check_script() {
+    let script = "function main(config) { return config; }";
+    assert!(check_script(script).is_ok());
+    let script = "import { a } from 'lib';\nexport const main = (config) => a(config);";
+    assert!(check_script(script).is_ok());
+
+    let html = "<html><body><main>502 Bad Gateway</main></body></html>";
+    assert!(check_script(html).is_err());
+    assert!(check_script("function main(config) { return config;").is_err());
+    // only mentions `main`
+    assert!(check_script("// the main function is removed").is_err());
+}
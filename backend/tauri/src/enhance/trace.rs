@@ -0,0 +1,130 @@
+//! record the config after every stage of `enhance`, for the dry run
+use super::{ResultLog, ValidationError};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Mapping;
+use similar::TextDiff;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StageKind {
+    /// the current profile is loaded
+    Profile,
+    /// the fields are filtered by the valid or the clash fields
+    Filter,
+    Merge,
+    Script,
+    /// the guarded fields are overwritten by the clash config
+    HandleFields,
+    Builtin,
+    Tun,
+    Sort,
+}
+
+/// the changes of a stage, the keys are the top-level fields
+#[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct StageDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+    /// the unified diff of the yaml
+    pub patch: String,
+}
+
+impl StageDiff {
+    fn new(old: &Mapping, new: &Mapping, old_yaml: &str, new_yaml: &str) -> Self {
+        let keys = |config: &Mapping| {
+            config
+                .keys()
+                .filter_map(|k| k.as_str().map(String::from))
+                .collect::<Vec<_>>()
+        };
+        let mut diff = Self::default();
+        for key in keys(new) {
+            match old.get(&key) {
+                None => diff.added.push(key),
+                Some(value) if Some(value) != new.get(&key) => diff.modified.push(key),
+                _ => {}
+            }
+        }
+        diff.removed = keys(old)
+            .into_iter()
+            .filter(|key| !new.contains_key(key))
+            .collect();
+        if old_yaml != new_yaml {
+            diff.patch = TextDiff::from_lines(old_yaml, new_yaml)
+                .unified_diff()
+                .context_radius(3)
+                .to_string();
+        }
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patch.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TraceStage {
+    pub kind: StageKind,
+    /// the uid of the chain item or the builtin script
+    pub uid: Option<String>,
+    pub diff: StageDiff,
+}
+
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct EnhanceTrace {
+    pub stages: Vec<TraceStage>,
+    /// the final config, which is not applied
+    pub config: Mapping,
+    pub logs: HashMap<String, ResultLog>,
+    pub errors: Vec<ValidationError>,
+    #[serde(skip)]
+    last: Mapping,
+    #[serde(skip)]
+    last_yaml: String,
+}
+
+impl EnhanceTrace {
+    /// record the config after the stage, diffed with the previous one
+    pub fn record(&mut self, kind: StageKind, uid: Option<&str>, config: &Mapping) {
+        let yaml = serde_yaml::to_string(config).unwrap_or_default();
+        let diff = StageDiff::new(&self.last, config, &self.last_yaml, &yaml);
+        self.stages.push(TraceStage {
+            kind,
+            uid: uid.map(String::from),
+            diff,
+        });
+        self.last = config.clone();
+        self.last_yaml = yaml;
+    }
+}
+
+#[test]
+fn test_enhance_trace() {
+    let config = |yaml: &str| serde_yaml::from_str::<Mapping>(yaml).unwrap();
+    let mut trace = EnhanceTrace::default();
+
+    trace.record(StageKind::Profile, None, &config("mode: rule\nproxies: []"));
+    trace.record(
+        StageKind::Merge,
+        Some("merge"),
+        &config("mode: global\nrules: []"),
+    );
+    trace.record(StageKind::Sort, None, &config("mode: global\nrules: []"));
+
+    let profile = &trace.stages[0].diff;
+    assert_eq!(profile.added, ["mode", "proxies"]);
+    assert!(profile.patch.contains("+mode: rule"));
+
+    let merge = &trace.stages[1];
+    assert_eq!(merge.uid.as_deref(), Some("merge"));
+    assert_eq!(merge.diff.added, ["rules"]);
+    assert_eq!(merge.diff.removed, ["proxies"]);
+    assert_eq!(merge.diff.modified, ["mode"]);
+    assert!(merge.diff.patch.contains("-mode: rule"));
+    assert!(merge.diff.patch.contains("+mode: global"));
+
+    assert!(trace.stages[2].diff.is_empty());
+}
@@ -0,0 +1,357 @@
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::{HashMap, HashSet};
+
+/// the policies which are always available
+const BUILTIN_POLICIES: [&str; 6] = [
+    "DIRECT",
+    "REJECT",
+    "REJECT-DROP",
+    "PASS",
+    "COMPATIBLE",
+    "GLOBAL",
+];
+
+/// the rule types of mihomo, except `MATCH`
+const RULE_TYPES: [&str; 34] = [
+    "DOMAIN",
+    "DOMAIN-SUFFIX",
+    "DOMAIN-KEYWORD",
+    "DOMAIN-WILDCARD",
+    "DOMAIN-REGEX",
+    "GEOSITE",
+    "GEOIP",
+    "IP-CIDR",
+    "IP-CIDR6",
+    "IP-SUFFIX",
+    "IP-ASN",
+    "SRC-GEOIP",
+    "SRC-IP-ASN",
+    "SRC-IP-CIDR",
+    "SRC-IP-SUFFIX",
+    "DST-PORT",
+    "SRC-PORT",
+    "IN-PORT",
+    "IN-TYPE",
+    "IN-USER",
+    "IN-NAME",
+    "PROCESS-PATH",
+    "PROCESS-PATH-REGEX",
+    "PROCESS-NAME",
+    "PROCESS-NAME-REGEX",
+    "UID",
+    "NETWORK",
+    "DSCP",
+    "RULE-SET",
+    "AND",
+    "OR",
+    "NOT",
+    "SUB-RULE",
+    "SCRIPT",
+];
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ValidationKind {
+    /// the proxy or group name is used more than once
+    DuplicateName,
+    /// the group member is neither a proxy nor a group
+    MissingMember,
+    /// the rule targets an undefined policy
+    UndefinedPolicy,
+    MalformedRule,
+    CyclicGroup,
+}
+
+impl ValidationKind {
+    /// whether the config is refused before the core checks it,
+    /// the rules are left to the core, which may support more rule types
+    pub fn is_blocking(&self) -> bool {
+        matches!(
+            self,
+            Self::DuplicateName | Self::MissingMember | Self::CyclicGroup
+        )
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ValidationError {
+    pub kind: ValidationKind,
+    pub message: String,
+    /// the uid of the profile, merge or script which introduced it
+    pub source: Option<String>,
+}
+
+/// record which chain item introduced the proxies, groups and rules
+#[derive(Debug, Default, Clone)]
+pub struct ConfigSources {
+    proxies: HashMap<String, String>,
+    groups: HashMap<String, String>,
+    rules: HashMap<String, String>,
+}
+
+impl ConfigSources {
+    /// the items which are added or changed in `after` belong to `uid`
+    pub fn track(&mut self, uid: &str, before: Option<&Mapping>, after: &Mapping) {
+        let empty = Mapping::new();
+        let before = before.unwrap_or(&empty);
+
+        for (key, map) in [
+            ("proxies", &mut self.proxies),
+            ("proxy-groups", &mut self.groups),
+        ] {
+            let old = named_items(before.get(key))
+                .into_iter()
+                .collect::<HashMap<_, _>>();
+            for (name, item) in named_items(after.get(key)) {
+                if old.get(name) != Some(&item) {
+                    map.insert(name.to_string(), uid.to_string());
+                }
+            }
+        }
+
+        let old = rule_items(before).collect::<HashSet<_>>();
+        for rule in rule_items(after).filter(|r| !old.contains(r)) {
+            self.rules.insert(rule.to_string(), uid.to_string());
+        }
+    }
+
+    fn proxy(&self, name: &str) -> Option<String> {
+        self.proxies.get(name).cloned()
+    }
+
+    fn group(&self, name: &str) -> Option<String> {
+        self.groups.get(name).cloned()
+    }
+
+    fn rule(&self, rule: &str) -> Option<String> {
+        self.rules.get(rule).cloned()
+    }
+}
+
+fn named_items(value: Option<&Value>) -> Vec<(&str, &Value)> {
+    value
+        .and_then(Value::as_sequence)
+        .map(|seq| {
+            seq.iter()
+                .filter_map(|item| Some((item.get("name")?.as_str()?, item)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn rule_items(config: &Mapping) -> impl Iterator<Item = &str> {
+    config
+        .get("rules")
+        .and_then(Value::as_sequence)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+}
+
+/// split the rule by the commas outside the parentheses,
+/// e.g. `AND,((DOMAIN,a.com),(NETWORK,UDP)),REJECT`
+fn split_rule(rule: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in rule.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(rule[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(rule[start..].trim());
+    parts
+}
+
+/// check the final config before it's applied to the core
+pub fn use_validate(config: &Mapping, sources: &ConfigSources) -> Vec<ValidationError> {
+    let mut errors = vec![];
+    let mut error = |kind, message, source| {
+        errors.push(ValidationError {
+            kind,
+            message,
+            source,
+        })
+    };
+
+    let proxies = named_items(config.get("proxies"));
+    let groups = named_items(config.get("proxy-groups"));
+
+    // duplicate names
+    let mut names = HashSet::new();
+    for (name, _) in proxies.iter() {
+        if !names.insert(*name) {
+            let msg = format!("duplicate proxy name `{name}`");
+            error(ValidationKind::DuplicateName, msg, sources.proxy(name));
+        }
+    }
+    for (name, _) in groups.iter() {
+        if !names.insert(*name) {
+            let msg = format!("duplicate proxy group name `{name}`");
+            error(ValidationKind::DuplicateName, msg, sources.group(name));
+        }
+    }
+
+    let mut policies = names;
+    policies.extend(BUILTIN_POLICIES);
+
+    // the members of the groups
+    let group_names = groups.iter().map(|(n, _)| *n).collect::<HashSet<_>>();
+    let mut graph: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (name, group) in groups.iter() {
+        let members = group.get("proxies").and_then(Value::as_sequence);
+        for member in members.into_iter().flatten().filter_map(Value::as_str) {
+            if !policies.contains(member) {
+                let msg = format!("group `{name}` refers to the missing proxy `{member}`");
+                error(ValidationKind::MissingMember, msg, sources.group(name));
+            }
+            if group_names.contains(member) {
+                graph.entry(name).or_default().push(member);
+            }
+        }
+    }
+
+    // cyclic references of the groups
+    for cycle in find_cycles(&groups, &graph) {
+        let msg = format!("cyclic proxy groups `{}`", cycle.join(" -> "));
+        error(ValidationKind::CyclicGroup, msg, sources.group(cycle[0]));
+    }
+
+    // the rules
+    let sub_rules = config.get("sub-rules").and_then(Value::as_mapping);
+    let rules = config.get("rules").and_then(Value::as_sequence);
+    for rule in rules.into_iter().flatten() {
+        let Some(text) = rule.as_str() else {
+            let msg = format!("the rule `{rule:?}` is not a string");
+            error(ValidationKind::MalformedRule, msg, None);
+            continue;
+        };
+
+        let parts = split_rule(text);
+        let target = match parts[0].to_uppercase().as_str() {
+            "MATCH" if parts.len() >= 2 => parts[1],
+            typ if RULE_TYPES.contains(&typ) && parts.len() >= 3 => parts[2],
+            _ => {
+                let msg = format!("malformed rule `{text}`");
+                error(ValidationKind::MalformedRule, msg, sources.rule(text));
+                continue;
+            }
+        };
+
+        let defined = match parts[0].eq_ignore_ascii_case("SUB-RULE") {
+            true => sub_rules.map_or(false, |s| s.contains_key(target)),
+            false => policies.contains(target),
+        };
+        if !defined {
+            let msg = format!("rule `{text}` targets the undefined policy `{target}`");
+            error(ValidationKind::UndefinedPolicy, msg, sources.rule(text));
+        }
+    }
+
+    errors
+}
+
+/// find the cycles with the dfs, each cycle is reported once
+fn find_cycles<'a>(
+    groups: &[(&'a str, &Value)],
+    graph: &HashMap<&'a str, Vec<&'a str>>,
+) -> Vec<Vec<&'a str>> {
+    fn visit<'a>(
+        node: &'a str,
+        graph: &HashMap<&'a str, Vec<&'a str>>,
+        path: &mut Vec<&'a str>,
+        done: &mut HashSet<&'a str>,
+        cycles: &mut Vec<Vec<&'a str>>,
+    ) {
+        if let Some(index) = path.iter().position(|n| *n == node) {
+            let mut cycle = path[index..].to_vec();
+            cycle.push(node);
+            cycles.push(cycle);
+            return;
+        }
+        if done.contains(node) {
+            return;
+        }
+
+        path.push(node);
+        for next in graph.get(node).into_iter().flatten() {
+            visit(next, graph, path, done, cycles);
+        }
+        path.pop();
+        done.insert(node);
+    }
+
+    let mut cycles = vec![];
+    let mut done = HashSet::new();
+    for (name, _) in groups.iter() {
+        visit(name, graph, &mut vec![], &mut done, &mut cycles);
+    }
+    cycles
+}
+
+#[test]
+fn test_validate() -> anyhow::Result<()> {
+    let profile = r#"
+proxies:
+  - { name: hk, type: ss, server: hk.example.com, port: 8388 }
+  - { name: jp, type: ss, server: jp.example.com, port: 8388 }
+proxy-groups:
+  - { name: PROXY, type: select, proxies: [AUTO, hk, jp] }
+  - { name: AUTO, type: url-test, proxies: [hk, jp] }
+rules:
+  - AND,((DOMAIN,a.com),(NETWORK,UDP)),REJECT
+  - DOMAIN-SUFFIX,google.com,PROXY
+  - DOMAIN-WILDCARD,*.google.com,PROXY
+  - MATCH,PROXY
+"#;
+    let merge = r#"
+proxies:
+  - { name: hk, type: ss, server: hk.example.com, port: 8388 }
+  - { name: jp, type: ss, server: jp.example.com, port: 8388 }
+  - { name: jp, type: ss, server: jp2.example.com, port: 8388 }
+proxy-groups:
+  - { name: PROXY, type: select, proxies: [AUTO, hk, jp, sg] }
+  - { name: AUTO, type: url-test, proxies: [hk, jp, LOOP] }
+  - { name: LOOP, type: select, proxies: [PROXY] }
+rules:
+  - AND,((DOMAIN,a.com),(NETWORK,UDP)),REJECT
+  - DOMAIN-SUFFIX,google.com,PROXY
+  - DOMAIN-SUFFIX,example.com,Streaming
+  - DOMAIN-SUFFIX,example.com
+  - MATCH,PROXY
+"#;
+    let profile = serde_yaml::from_str::<Mapping>(profile)?;
+    let merge = serde_yaml::from_str::<Mapping>(merge)?;
+
+    let mut sources = ConfigSources::default();
+    sources.track("profile", None, &profile);
+    assert!(use_validate(&profile, &sources).is_empty());
+
+    sources.track("merge", Some(&profile), &merge);
+    let errors = use_validate(&merge, &sources);
+    let kinds = errors.iter().map(|e| e.kind).collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        vec![
+            ValidationKind::DuplicateName,
+            ValidationKind::MissingMember,
+            ValidationKind::CyclicGroup,
+            ValidationKind::UndefinedPolicy,
+            ValidationKind::MalformedRule,
+        ]
+    );
+    assert_eq!(
+        errors[2].message,
+        "cyclic proxy groups `PROXY -> AUTO -> LOOP -> PROXY`"
+    );
+    assert!(errors.iter().all(|e| e.source.as_deref() == Some("merge")));
+    assert!(!errors[3].kind.is_blocking() && !errors[4].kind.is_blocking());
+    Ok(())
+}
@@ -6,11 +6,11 @@
 //!
 use crate::{
     config::*,
-    core::*,
+    core::{revisions::RevisionSource, tasks::ConfigChangedNotifier, *},
     log_err,
     utils::{self, help::get_clash_external_port, resolve},
 };
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use serde_yaml::{Mapping, Value};
 use wry::application::clipboard::Clipboard;
 
@@ -264,6 +264,9 @@ pub async fn patch_clash(patch: Mapping) -> Result<()> {
 /// 修改verge的配置
 /// 一般都是一个个的修改
 pub async fn patch_verge(patch: IVerge) -> Result<()> {
+    for action in patch.scheduled_actions.iter().flatten() {
+        action.validate()?;
+    }
     Config::verge().draft().patch_config(patch.clone());
     let tun_mode = patch.enable_tun_mode;
     let auto_launch = patch.enable_auto_launch;
@@ -273,6 +276,9 @@ pub async fn patch_verge(patch: IVerge) -> Result<()> {
     let log_level = patch.app_log_level;
     let log_max_files = patch.max_log_files;
     let enable_tray_selector = patch.clash_tray_selector;
+    let webdav_changed = patch.webdav_url.is_some() || patch.webdav_sync_interval.is_some();
+    let encrypt_secrets = patch.enable_encrypt_secrets;
+    let actions_changed = patch.scheduled_actions.is_some();
 
     let res = || async move {
         #[cfg(target_os = "windows")]
@@ -332,6 +338,22 @@ pub async fn patch_verge(patch: IVerge) -> Result<()> {
         Ok(()) => {
             Config::verge().apply();
             Config::verge().data().save_file()?;
+            if let Some(encrypt_secrets) = encrypt_secrets {
+                // rewrite the files with the secrets encrypted or decrypted
+                utils::crypto::set_enabled(encrypt_secrets);
+                Config::profiles().data().save_file()?;
+                Config::clash().data().save_config()?;
+            }
+            if webdav_changed {
+                log_err!(tasks::JobsManager::global()
+                    .lock()
+                    .notify_config_changed("webdav_sync"));
+            }
+            if actions_changed {
+                tasks::jobs::ScheduledActionsGuard::global()
+                    .lock()
+                    .refresh();
+            }
             Ok(())
         }
         Err(err) => {
@@ -343,40 +365,218 @@ pub async fn patch_verge(patch: IVerge) -> Result<()> {
 
 /// 更新某个profile
 /// 如果更新当前配置就激活配置
-pub async fn update_profile(uid: String, option: Option<PrfOption>) -> Result<()> {
-    let url_opt = {
+/// 返回远程订阅的变更
+pub async fn update_profile(
+    uid: String,
+    option: Option<PrfOption>,
+) -> Result<Option<ProfileChanges>> {
+    let item = {
         let profiles = Config::profiles();
         let profiles = profiles.latest();
-        let item = profiles.get_item(&uid)?;
-        let is_remote = item.itype.as_ref().map_or(false, |s| s == "remote");
+        profiles.get_item(&uid)?.clone()
+    };
 
-        if !is_remote {
-            None // 直接更新
-        } else if item.url.is_none() {
-            bail!("failed to get the profile item url");
-        } else {
-            Some((item.url.clone().unwrap(), item.option.clone()))
+    let (should_update, changes) = match item.itype.as_deref() {
+        Some("remote") => match update_remote_item(&item, option).await? {
+            Some(changes) => {
+                // 重新生成使用该订阅的聚合配置
+                let aggregated = regenerate_aggregates(&uid);
+                (is_current_profile(&uid) || aggregated, changes)
+            }
+            None => (false, None),
+        },
+        Some("aggregate") => {
+            let sources = item.aggregate.as_ref().map(|a| a.sources.clone());
+            // 更新的订阅本身也可能是当前配置
+            let mut source_current = false;
+            for source_uid in sources.unwrap_or_default() {
+                let source = {
+                    let profiles = Config::profiles();
+                    let profiles = profiles.latest();
+                    profiles.get_item(&source_uid).ok().cloned()
+                };
+                if let Some(source) = source.filter(|s| s.itype.as_deref() == Some("remote")) {
+                    match update_remote_item(&source, None).await {
+                        Ok(Some(_)) => source_current |= is_current_profile(&source_uid),
+                        Ok(None) => {}
+                        Err(err) => log::error!(target: "app", "{err}"),
+                    }
+                }
+            }
+            let changes = regenerate_aggregate(&uid)?;
+            (is_current_profile(&uid) || source_current, changes)
+        }
+        Some("merge" | "script") if item.url.is_some() => {
+            let modified = update_enhance_item(&item, option).await?;
+            (modified && is_chain_item(&uid), None)
         }
+        _ => (true, None), // 直接更新
     };
 
-    let should_update = match url_opt {
-        Some((url, opt)) => {
-            let merged_opt = PrfOption::merge(opt, option);
-            let item = PrfItem::from_url(&url, None, None, merged_opt).await?;
+    if should_update {
+        update_core_config().await?;
+    }
 
-            let profiles = Config::profiles();
-            let mut profiles = profiles.latest();
+    Ok(changes)
+}
+
+/// 下载远程订阅并保存
+/// 订阅未修改时返回 `None`
+async fn update_remote_item(
+    cached: &PrfItem,
+    option: Option<PrfOption>,
+) -> Result<Option<Option<ProfileChanges>>> {
+    let uid = cached.uid.clone().unwrap_or_default();
+    let url = match cached.url.as_ref() {
+        Some(url) => url,
+        None => bail!("failed to get the profile item url"),
+    };
+    let merged_opt = PrfOption::merge(cached.option.clone(), option);
+    let fetched = PrfItem::from_url_cached(url, merged_opt, cached).await?;
+
+    let profiles = Config::profiles();
+    let mut profiles = profiles.latest();
+    match fetched {
+        PrfFetched::Modified(item) => {
+            let old_data = cached.read_file().ok();
+            let data = item.file_data.clone().unwrap_or_default();
+            record_revision(&uid, old_data.as_deref(), &data);
             profiles.update_item(uid.clone(), item)?;
 
-            Some(uid) == profiles.get_current()
+            let changes = profile_changes(old_data, &data);
+            notice_changes(cached, changes.as_ref());
+            Ok(Some(changes))
+        }
+        // not modified, only refresh the fields from the headers and keep the core as is
+        PrfFetched::NotModified(patch) => {
+            profiles.patch_item(uid, patch)?;
+            Ok(None)
         }
-        None => true,
+    }
+}
+
+/// 下载远程的 merge / script 并保存，返回内容是否有变化
+/// 下载或校验失败时保留上一次的文件
+async fn update_enhance_item(cached: &PrfItem, option: Option<PrfOption>) -> Result<bool> {
+    let uid = cached.uid.clone().unwrap_or_default();
+    let itype = cached.itype.as_deref().unwrap_or_default();
+    let url = match cached.url.as_ref() {
+        Some(url) => url,
+        None => bail!("failed to get the profile item url"),
     };
+    let merged_opt = PrfOption::merge(cached.option.clone(), option);
+    let (data, transport) =
+        PrfItem::fetch_enhance(itype, url, merged_opt.as_ref(), cached.transport)
+            .await
+            .with_context(|| format!("failed to update `{uid}`, keep the last copy"))?;
+
+    let old_data = cached.read_file().ok();
+    let modified = old_data.as_deref() != Some(data.as_str());
+    if modified {
+        record_revision(&uid, old_data.as_deref(), &data);
+    }
+    let patch = PrfItem {
+        updated: Some(chrono::Local::now().timestamp() as usize),
+        transport: Some(transport),
+        file_data: modified.then_some(data),
+        ..PrfItem::default()
+    };
+    Config::profiles().latest().update_item(uid, patch)?;
+    Ok(modified)
+}
 
-    if should_update {
-        update_core_config().await?;
+/// 重新生成聚合配置
+fn regenerate_aggregate(uid: &str) -> Result<Option<ProfileChanges>> {
+    let profiles = Config::profiles();
+    let mut profiles = profiles.latest();
+    let item = profiles.get_item(&uid.to_string())?.clone();
+    let aggregate = match item.aggregate.as_ref() {
+        Some(aggregate) => aggregate,
+        None => bail!("failed to get the aggregate sources"),
+    };
+    let (data, _) = profiles.generate_aggregate(aggregate)?;
+
+    let old_data = item.read_file().ok();
+    record_revision(uid, old_data.as_deref(), &data);
+    let patch = PrfItem {
+        updated: Some(chrono::Local::now().timestamp() as usize),
+        file_data: Some(data.clone()),
+        ..PrfItem::default()
+    };
+    profiles.update_item(uid.to_string(), patch)?;
+
+    let changes = profile_changes(old_data, &data);
+    notice_changes(&item, changes.as_ref());
+    Ok(changes)
+}
+
+/// 重新生成使用该订阅的聚合配置，返回是否包含当前配置
+fn regenerate_aggregates(source: &str) -> bool {
+    let aggregates = Config::profiles().latest().get_aggregates_of(source);
+    let mut has_current = false;
+    for uid in aggregates {
+        match regenerate_aggregate(&uid) {
+            Ok(_) => has_current |= is_current_profile(&uid),
+            Err(err) => log::error!(target: "app", "failed to regenerate `{uid}`: {err}"),
+        }
+    }
+    has_current
+}
+
+fn is_current_profile(uid: &str) -> bool {
+    Config::profiles().latest().get_current().as_deref() == Some(uid)
+}
+
+fn is_chain_item(uid: &str) -> bool {
+    let profiles = Config::profiles();
+    let profiles = profiles.latest();
+    profiles.active_chain().iter().any(|c| c == uid)
+}
+
+fn record_revision(uid: &str, old_data: Option<&str>, data: &str) {
+    let revisions = revisions::ProfileRevisions::global();
+    if let Some(old_data) = old_data {
+        log_err!(revisions.record_base(uid, old_data));
+    }
+    log_err!(revisions.record(uid, RevisionSource::RemoteUpdate, data));
+}
+
+fn notice_changes(item: &PrfItem, changes: Option<&ProfileChanges>) {
+    if let Some(changes) = changes.filter(|c| !c.is_empty()) {
+        let uid = item.uid.clone().unwrap_or_default();
+        let name = item.name.as_ref().unwrap_or(&uid);
+        let msg = format!("{name}: {}", changes.summary());
+        handle::Handle::notice_message("update_profile::changes", msg);
     }
+}
 
+/// 比较更新前后的订阅内容
+fn profile_changes(old: Option<String>, new: &str) -> Option<ProfileChanges> {
+    let old = serde_yaml::from_str::<Mapping>(&old?).ok()?;
+    let new = serde_yaml::from_str::<Mapping>(new).ok()?;
+    Some(ProfileChanges::diff(&old, &new))
+}
+
+/// 恢复配置文件到历史版本
+pub async fn restore_profile_revision(uid: String, id: u64) -> Result<()> {
+    let revisions = revisions::ProfileRevisions::global();
+    let data = revisions.get(&uid, id)?.data.unwrap_or_default();
+
+    let is_current = {
+        let profiles = Config::profiles();
+        let profiles = profiles.latest();
+        let item = profiles.get_item(&uid)?;
+        log_err!(item
+            .read_file()
+            .and_then(|old| revisions.record_base(&uid, &old)));
+        item.save_file(data.clone())?;
+        Some(uid.clone()) == profiles.get_current()
+    };
+    log_err!(revisions.record(&uid, RevisionSource::Rollback, &data));
+
+    if is_current {
+        update_core_config().await?;
+    }
     Ok(())
 }
 
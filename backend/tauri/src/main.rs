@@ -131,6 +131,9 @@ fn main() -> std::io::Result<()> {
             cmds::change_clash_core,
             cmds::get_runtime_config,
             cmds::get_runtime_yaml,
+            cmds::get_runtime_export,
+            cmds::get_runtime_validation,
+            cmds::dry_run_enhance,
             cmds::get_runtime_exists,
             cmds::get_runtime_logs,
             cmds::clash_api_get_proxy_delay,
@@ -141,6 +144,10 @@ fn main() -> std::io::Result<()> {
             cmds::get_core_version,
             // utils
             cmds::collect_logs,
+            cmds::create_backup,
+            cmds::restore_backup,
+            cmds::webdav_sync,
+            cmds::unlock_secrets,
             // verge
             cmds::get_verge_config,
             cmds::patch_verge_config,
@@ -158,6 +165,9 @@ fn main() -> std::io::Result<()> {
             cmds::delete_profile,
             cmds::read_profile_file,
             cmds::save_profile_file,
+            cmds::get_profile_revisions,
+            cmds::get_profile_revision_diff,
+            cmds::restore_profile_revision,
             cmds::save_window_size_state,
             cmds::get_custom_app_dir,
             cmds::set_custom_app_dir,
@@ -0,0 +1,207 @@
+//! encrypt the secrets in the config files at rest, e.g. the subscription urls
+use super::dirs;
+use anyhow::{anyhow, bail, Context, Result};
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use parking_lot::Mutex;
+use std::{
+    fs,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+};
+
+/// the prefix of the encrypted values
+const ENCRYPTED_PREFIX: &str = "enc:v1:";
+
+const NONCE_SIZE: usize = 24;
+
+const KEYRING_USER: &str = "config-secrets";
+
+/// the salt of the passphrase-derived key
+const SALT_FILE: &str = "secrets.salt";
+
+/// provide the passphrase when no keyring is available
+pub const PASSPHRASE_ENV: &str = "SHADOWROCKET_PASSPHRASE";
+
+pub type SecretKey = [u8; 32];
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+fn cached_key() -> &'static Mutex<Option<SecretKey>> {
+    static KEY: OnceLock<Mutex<Option<SecretKey>>> = OnceLock::new();
+    KEY.get_or_init(|| Mutex::new(None))
+}
+
+/// whether to encrypt the secrets when saving the files,
+/// the encrypted values are always decrypted when loading
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn is_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX)
+}
+
+/// use the key derived from the passphrase, for the systems without a keyring
+pub fn set_passphrase(passphrase: &str) -> Result<()> {
+    let key = derive_key(passphrase, &load_salt()?)?;
+    *cached_key().lock() = Some(key);
+    Ok(())
+}
+
+/// encrypt the value if the encryption is enabled
+pub fn encrypt(value: &str) -> Result<String> {
+    if !is_enabled() || is_encrypted(value) {
+        return Ok(value.to_string());
+    }
+    encrypt_with(&key()?, value)
+}
+
+/// decrypt the value if it's encrypted
+pub fn decrypt(value: &str) -> Result<String> {
+    if !is_encrypted(value) {
+        return Ok(value.to_string());
+    }
+    decrypt_with(&key()?, value)
+}
+
+pub fn encrypt_with(key: &SecretKey, value: &str) -> Result<String> {
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(key));
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let data = cipher
+        .encrypt(&nonce, value.as_bytes())
+        .map_err(|_| anyhow!("failed to encrypt the secret"))?;
+
+    let mut sealed = nonce.to_vec();
+    sealed.extend(data);
+    Ok(format!("{ENCRYPTED_PREFIX}{}", STANDARD.encode(sealed)))
+}
+
+pub fn decrypt_with(key: &SecretKey, value: &str) -> Result<String> {
+    let Some(encoded) = value.strip_prefix(ENCRYPTED_PREFIX) else {
+        bail!("the secret is not encrypted");
+    };
+    let sealed = STANDARD
+        .decode(encoded)
+        .context("the encrypted secret is malformed")?;
+    if sealed.len() < NONCE_SIZE {
+        bail!("the encrypted secret is malformed");
+    }
+
+    let (nonce, data) = sealed.split_at(NONCE_SIZE);
+    let cipher = XChaCha20Poly1305::new(Key::from_slice(key));
+    let data = cipher
+        .decrypt(XNonce::from_slice(nonce), data)
+        .map_err(|_| anyhow!("failed to decrypt the secret, the key may be changed"))?;
+    Ok(String::from_utf8(data)?)
+}
+
+pub fn derive_key(passphrase: &str, salt: &[u8]) -> Result<SecretKey> {
+    let mut key = SecretKey::default();
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|err| anyhow!("failed to derive the key: {err}"))?;
+    Ok(key)
+}
+
+/// the key from the cache, the OS keyring or the passphrase in the env
+fn key() -> Result<SecretKey> {
+    let mut cached = cached_key().lock();
+    if let Some(key) = cached.as_ref() {
+        return Ok(*key);
+    }
+
+    let key = match keyring_key() {
+        Ok(key) => key,
+        Err(err) => {
+            log::warn!(target: "app", "failed to use the keyring: {err}");
+            match std::env::var(PASSPHRASE_ENV) {
+                Ok(passphrase) if !passphrase.is_empty() => derive_key(&passphrase, &load_salt()?)?,
+                _ => return Err(err).context("no key is available, please provide the passphrase"),
+            }
+        }
+    };
+    *cached = Some(key);
+    Ok(key)
+}
+
+/// load the key from the OS keyring, create it for the first time,
+/// never replace the lost key which the saved secrets are encrypted with
+fn keyring_key() -> Result<SecretKey> {
+    let entry = keyring::Entry::new(dirs::APP_NAME, KEYRING_USER)?;
+    match entry.get_password() {
+        Ok(encoded) => {
+            let bytes = STANDARD.decode(encoded)?;
+            SecretKey::try_from(bytes.as_slice()).context("the key in the keyring is malformed")
+        }
+        Err(keyring::Error::NoEntry) => {
+            if has_encrypted_files() {
+                bail!(
+                    "the key of the encrypted secrets is missing in the keyring, \
+                     restore the old key or provide the passphrase"
+                );
+            }
+            let key = XChaCha20Poly1305::generate_key(&mut OsRng);
+            entry.set_password(&STANDARD.encode(key))?;
+            let mut bytes = SecretKey::default();
+            bytes.copy_from_slice(&key);
+            Ok(bytes)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// whether the config files contain the encrypted secrets
+fn has_encrypted_files() -> bool {
+    [dirs::profiles_path(), dirs::clash_path()]
+        .into_iter()
+        .flatten()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .any(|content| content.contains(ENCRYPTED_PREFIX))
+}
+
+/// a random salt for `derive_key`
+pub fn generate_salt() -> Vec<u8> {
+    let mut salt = vec![0; 16];
+    OsRng.fill_bytes(&mut salt);
+    salt
+}
+
+fn load_salt() -> Result<Vec<u8>> {
+    let path = dirs::app_home_dir()?.join(SALT_FILE);
+    if path.exists() {
+        return Ok(fs::read(&path)?);
+    }
+
+    let salt = generate_salt();
+    fs::write(&path, &salt).context("failed to save the salt")?;
+    Ok(salt)
+}
+
+#[test]
+fn test_encrypt_secret() -> Result<()> {
+    let key = derive_key("passphrase", b"shadowrocket-salt")?;
+    let url = "https://example.com/sub?token=secret";
+
+    let encrypted = encrypt_with(&key, url)?;
+    assert!(is_encrypted(&encrypted));
+    assert!(!encrypted.contains("secret"));
+    // the nonce is random
+    assert_ne!(encrypted, encrypt_with(&key, url)?);
+    assert_eq!(decrypt_with(&key, &encrypted)?, url);
+
+    let other = derive_key("other", b"shadowrocket-salt")?;
+    assert!(decrypt_with(&other, &encrypted).is_err());
+    assert!(decrypt_with(&key, "enc:v1:AAAA").is_err());
+    Ok(())
+}
@@ -1,5 +1,6 @@
 pub mod candy;
 pub mod config;
+pub mod crypto;
 pub mod dialog;
 pub mod dirs;
 pub mod help;
@@ -4,12 +4,15 @@ use crate::{
         Config, IVerge,
     },
     core::{
-        tasks::{jobs::ProfilesJobGuard, JobsManager},
+        tasks::{
+            jobs::{ProfilesJobGuard, ScheduledActionsGuard},
+            JobsManager,
+        },
         tray::proxies,
         *,
     },
     log_err, trace_err,
-    utils::init,
+    utils::{crypto, init},
 };
 use anyhow::Result;
 use semver::Version;
@@ -50,6 +53,15 @@ pub fn resolve_setup(app: &mut App) {
     #[cfg(target_os = "windows")]
     log_err!(init::init_service());
 
+    // 按设置加密或解密配置文件中的敏感字段，config.yaml 会在下面保存
+    crypto::set_enabled(
+        Config::verge()
+            .latest()
+            .enable_encrypt_secrets
+            .unwrap_or(false),
+    );
+    log_err!(Config::profiles().data().save_file());
+
     // 处理随机端口
     let enable_random_port = Config::verge().latest().enable_random_port.unwrap_or(false);
 
@@ -102,6 +114,9 @@ pub fn resolve_setup(app: &mut App) {
     log_err!(JobsManager::global_register());
     // init task manager
     log_err!(ProfilesJobGuard::global().lock().init());
+    ScheduledActionsGuard::global().lock().refresh();
+    log_err!(watcher::ProfilesWatcher::global().start());
+    profile_switch::ProfileSwitcher::global().start();
 
     // test job
     proxies::setup_proxies();
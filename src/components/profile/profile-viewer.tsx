@@ -26,7 +26,8 @@ import MDYSwitch from "../common/mdy-switch";
 import { FileInput } from "./file-input";
 
 interface Props {
-  onChange: () => void;
+  // with the warnings when the created profile is converted
+  onChange: (convertLogs?: [string, string][]) => void;
   url?: string;
   name?: string;
   desc?: string;
@@ -98,18 +99,33 @@ export const ProfileViewer = forwardRef<ProfileViewerRef, Props>(
           if (form.type === "remote" && !form.url) {
             throw new Error("The URL should not be null");
           }
-          if (form.type !== "remote" && form.type !== "local") {
+          const isEnhance = form.type === "merge" || form.type === "script";
+          if (isEnhance && !form.url) delete form.url;
+          if (form.type !== "remote" && form.type !== "local" && !form.url) {
             delete form.option;
           }
           if (form.option?.update_interval) {
             form.option.update_interval = +form.option.update_interval;
           }
+          if (form.option?.retry_times) {
+            form.option.retry_times = +form.option.retry_times;
+          }
+          if (form.option?.connect_timeout) {
+            form.option.connect_timeout = +form.option.connect_timeout;
+          }
+          if (form.option?.timeout) {
+            form.option.timeout = +form.option.timeout;
+          }
+          if (form.option?.basic_auth && !form.option.basic_auth.username) {
+            delete form.option.basic_auth;
+          }
           const name = form.name || `${form.type} file`;
           const item = { ...form, name };
 
+          let convertLogs: [string, string][] | undefined;
           // 创建
           if (openType === "new") {
-            await createProfile(item, fileDataRef.current);
+            convertLogs = await createProfile(item, fileDataRef.current);
           }
           // 编辑
           else {
@@ -120,7 +136,7 @@ export const ProfileViewer = forwardRef<ProfileViewerRef, Props>(
           setLoading(false);
           setTimeout(() => formIns.reset(), 500);
           fileDataRef.current = null;
-          props.onChange();
+          props.onChange(convertLogs);
         } catch (err: any) {
           useNotification({
             title: t("Error"),
@@ -150,6 +166,7 @@ export const ProfileViewer = forwardRef<ProfileViewerRef, Props>(
     const formType = watch("type");
     const isRemote = formType === "remote";
     const isLocal = formType === "local";
+    const isEnhance = formType === "merge" || formType === "script";
 
     return (
       <BaseDialog
@@ -222,10 +239,96 @@ export const ProfileViewer = forwardRef<ProfileViewerRef, Props>(
                 />
               )}
             />
+
+            <Controller
+              name="option.bearer_token"
+              control={control}
+              render={({ field }) => (
+                <TextField
+                  {...text}
+                  {...field}
+                  type="password"
+                  label={t("Bearer Token")}
+                />
+              )}
+            />
+
+            <Controller
+              name="option.basic_auth.username"
+              control={control}
+              render={({ field }) => (
+                <TextField {...text} {...field} label={t("Username")} />
+              )}
+            />
+
+            <Controller
+              name="option.basic_auth.password"
+              control={control}
+              render={({ field }) => (
+                <TextField
+                  {...text}
+                  {...field}
+                  type="password"
+                  label={t("Password")}
+                />
+              )}
+            />
+
+            <Controller
+              name="option.ca_cert"
+              control={control}
+              render={({ field }) => (
+                <TextField
+                  {...text}
+                  {...field}
+                  placeholder="/path/to/ca.pem"
+                  label={t("CA Certificate")}
+                />
+              )}
+            />
+
+            <Controller
+              name="option.client_cert"
+              control={control}
+              render={({ field }) => (
+                <TextField
+                  {...text}
+                  {...field}
+                  placeholder="/path/to/client.pem"
+                  label={t("Client Certificate")}
+                />
+              )}
+            />
+
+            <Controller
+              name="option.include"
+              control={control}
+              render={({ field }) => (
+                <TextField {...text} {...field} label={t("Include Proxies")} />
+              )}
+            />
+
+            <Controller
+              name="option.exclude"
+              control={control}
+              render={({ field }) => (
+                <TextField {...text} {...field} label={t("Exclude Proxies")} />
+              )}
+            />
           </>
         )}
 
-        {(isRemote || isLocal) && (
+        {isEnhance && (
+          <Controller
+            name="url"
+            control={control}
+            render={({ field }) => (
+              <TextField {...text} {...field} multiline label={t("URL")} />
+            )}
+          />
+        )}
+
+        {(isRemote || isLocal || isEnhance) && (
           <Controller
             name="option.update_interval"
             control={control}
@@ -250,6 +353,76 @@ export const ProfileViewer = forwardRef<ProfileViewerRef, Props>(
           />
         )}
 
+        {isRemote && (
+          <Controller
+            name="option.retry_times"
+            control={control}
+            render={({ field }) => (
+              <TextField
+                {...text}
+                {...field}
+                onChange={(e) => {
+                  e.target.value = e.target.value
+                    ?.replace(/\D/, "")
+                    .slice(0, 2);
+                  field.onChange(e);
+                }}
+                label={t("Retry Times")}
+              />
+            )}
+          />
+        )}
+
+        {isRemote && (
+          <Controller
+            name="option.connect_timeout"
+            control={control}
+            render={({ field }) => (
+              <TextField
+                {...text}
+                {...field}
+                onChange={(e) => {
+                  e.target.value = e.target.value
+                    ?.replace(/\D/, "")
+                    .slice(0, 3);
+                  field.onChange(e);
+                }}
+                label={t("Connect Timeout")}
+                InputProps={{
+                  endAdornment: (
+                    <InputAdornment position="end">secs</InputAdornment>
+                  ),
+                }}
+              />
+            )}
+          />
+        )}
+
+        {isRemote && (
+          <Controller
+            name="option.timeout"
+            control={control}
+            render={({ field }) => (
+              <TextField
+                {...text}
+                {...field}
+                onChange={(e) => {
+                  e.target.value = e.target.value
+                    ?.replace(/\D/, "")
+                    .slice(0, 4);
+                  field.onChange(e);
+                }}
+                label={t("Request Timeout")}
+                InputProps={{
+                  endAdornment: (
+                    <InputAdornment position="end">secs</InputAdornment>
+                  ),
+                }}
+              />
+            )}
+          />
+        )}
+
         {isLocal && openType === "new" && (
           <FileInput onChange={(val) => (fileDataRef.current = val)} />
         )}
@@ -277,6 +450,17 @@ export const ProfileViewer = forwardRef<ProfileViewerRef, Props>(
                 </StyledBox>
               )}
             />
+
+            <Controller
+              name="option.add_flag"
+              control={control}
+              render={({ field }) => (
+                <StyledBox>
+                  <InputLabel>{t("Add Region Flags")}</InputLabel>
+                  <MDYSwitch checked={field.value} {...field} color="primary" />
+                </StyledBox>
+              )}
+            />
           </>
         )}
       </BaseDialog>
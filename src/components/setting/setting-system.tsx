@@ -44,6 +44,8 @@ const SettingSystem = ({ onError }: Props) => {
     enable_service_mode,
     enable_silent_start,
     enable_system_proxy,
+    enable_encrypt_secrets,
+    enable_profile_switch,
   } = verge ?? {};
 
   const [loading, setLoading] = useState({
@@ -52,6 +54,8 @@ const SettingSystem = ({ onError }: Props) => {
     enable_service_mode: false,
     enable_silent_start: false,
     enable_system_proxy: false,
+    enable_encrypt_secrets: false,
+    enable_profile_switch: false,
   });
 
   const patchVergeWithLoading = async (value: Partial<IVergeConfig>) => {
@@ -178,6 +182,32 @@ const SettingSystem = ({ onError }: Props) => {
           <MDYSwitch edge="end" />
         </GuardState>
       </SettingItem>
+
+      <SettingItem label={t("Encrypt Secrets")}>
+        <GuardState
+          value={enable_encrypt_secrets ?? false}
+          valueProps="checked"
+          onCatch={onError}
+          onFormat={onSwitchFormat}
+          onGuard={(e) => patchVergeWithLoading({ enable_encrypt_secrets: e })}
+          loading={loading["enable_encrypt_secrets"]}
+        >
+          <MDYSwitch edge="end" />
+        </GuardState>
+      </SettingItem>
+
+      <SettingItem label={t("Auto Switch Profile")}>
+        <GuardState
+          value={enable_profile_switch ?? false}
+          valueProps="checked"
+          onCatch={onError}
+          onFormat={onSwitchFormat}
+          onGuard={(e) => patchVergeWithLoading({ enable_profile_switch: e })}
+          loading={loading["enable_profile_switch"]}
+        >
+          <MDYSwitch edge="end" />
+        </GuardState>
+      </SettingItem>
     </SettingList>
   );
 };
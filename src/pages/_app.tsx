@@ -92,6 +92,20 @@ export default function App() {
             type: NotificationType.Error,
           });
           break;
+        case "update_profile::changes":
+          useNotification({
+            title: t("Profile Updated"),
+            body: msg,
+            type: NotificationType.Info,
+          });
+          break;
+        case "webdav_sync::conflict":
+          useNotification({
+            title: t("WebDAV Sync Conflict"),
+            body: msg,
+            type: NotificationType.Error,
+          });
+          break;
         default:
           break;
       }
@@ -1,4 +1,5 @@
 import { BasePage, DialogRef } from "@/components/base";
+import { LogViewer } from "@/components/profile/log-viewer";
 import { ProfileItem } from "@/components/profile/profile-item";
 import { ProfileMore } from "@/components/profile/profile-more";
 import {
@@ -57,6 +58,8 @@ export default function ProfilePage() {
   const [disabled, setDisabled] = useState(false);
   const [activating, setActivating] = useState("");
   const [loading, setLoading] = useState(false);
+  // the warnings of the converted profile
+  const [convertLogs, setConvertLogs] = useState<[string, string][]>([]);
   const sensors = useSensors(
     useSensor(PointerSensor),
     useSensor(KeyboardSensor, {
@@ -111,7 +114,7 @@ export default function ProfilePage() {
     setLoading(true);
 
     try {
-      await importProfile(url);
+      setConvertLogs(await importProfile(url));
       useNotification({
         title: t("Success"),
         body: "Successfully import profile.",
@@ -433,9 +436,18 @@ export default function ProfilePage() {
         url={location.state?.subscribe?.url as string | undefined}
         name={location.state?.subscribe?.name as string | undefined}
         desc={location.state?.subscribe?.desc as string | undefined}
-        onChange={() => mutateProfiles()}
+        onChange={(logs) => {
+          mutateProfiles();
+          if (logs) setConvertLogs(logs);
+        }}
       />
       <ConfigViewer ref={configRef} />
+      <LogViewer
+        open={convertLogs.length > 0}
+        title={t("Convert Logs")}
+        logInfo={convertLogs}
+        onClose={() => setConvertLogs([])}
+      />
     </BasePage>
   );
 }
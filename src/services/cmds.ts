@@ -44,7 +44,7 @@ export async function createProfile(
   item: Partial<IProfileItem>,
   fileData?: string | null,
 ) {
-  return invoke<void>("create_profile", { item, fileData });
+  return invoke<[string, string][]>("create_profile", { item, fileData });
 }
 
 export async function viewProfile(index: string) {
@@ -59,8 +59,24 @@ export async function saveProfileFile(index: string, fileData: string) {
   return invoke<void>("save_profile_file", { index, fileData });
 }
 
+export async function getProfileRevisions(index: string) {
+  return invoke<IProfileRevision[]>("get_profile_revisions", { index });
+}
+
+export async function getProfileRevisionDiff(
+  index: string,
+  from: number,
+  to: number,
+) {
+  return invoke<string>("get_profile_revision_diff", { index, from, to });
+}
+
+export async function restoreProfileRevision(index: string, id: number) {
+  return invoke<void>("restore_profile_revision", { index, id });
+}
+
 export async function importProfile(url: string) {
-  return invoke<void>("import_profile", {
+  return invoke<[string, string][]>("import_profile", {
     url,
     option: { with_proxy: true },
   });
@@ -74,7 +90,7 @@ export async function reorderProfile(activeId: string, overId: string) {
 }
 
 export async function updateProfile(index: string, option?: IProfileOption) {
-  return invoke<void>("update_profile", { index, option });
+  return invoke<IProfileChanges | null>("update_profile", { index, option });
 }
 
 export async function deleteProfile(index: string) {
@@ -100,6 +116,20 @@ export async function getRuntimeYaml() {
   return invoke<string | null>("get_runtime_yaml");
 }
 
+export async function getRuntimeExport(
+  format: "shadowrocket" | "surge" | "quantumultx",
+) {
+  return invoke<IExportResult>("get_runtime_export", { format });
+}
+
+export async function getRuntimeValidation() {
+  return invoke<IValidationError[]>("get_runtime_validation");
+}
+
+export async function dryRunEnhance() {
+  return invoke<IEnhanceTrace>("dry_run_enhance");
+}
+
 export async function getRuntimeExists() {
   return invoke<string[]>("get_runtime_exists");
 }
@@ -235,6 +265,32 @@ export async function collectLogs() {
   return invoke<void>("collect_logs");
 }
 
+export async function createBackup(
+  path: string,
+  withStorage: boolean,
+  passphrase?: string,
+) {
+  return invoke<void>("create_backup", { path, withStorage, passphrase });
+}
+
+export async function restoreBackup(
+  path: string,
+  mode: "merge" | "replace",
+  passphrase?: string,
+) {
+  return invoke<void>("restore_backup", { path, mode, passphrase });
+}
+
+export async function unlockSecrets(passphrase: string) {
+  return invoke<void>("unlock_secrets", { passphrase });
+}
+
+export type WebDavSyncAction = "none" | "upload" | "download" | "conflict";
+
+export async function webdavSync(force?: "upload" | "download") {
+  return invoke<WebDavSyncAction>("webdav_sync", { force });
+}
+
 export async function cmdGetProxyDelay(name: string, url?: string) {
   name = encodeURIComponent(name);
   return invoke<{ delay: number }>("clash_api_get_proxy_delay", { name, url });
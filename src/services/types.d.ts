@@ -119,12 +119,15 @@ interface IClashInfo {
 
 interface IProfileItem {
   uid: string;
-  type?: "local" | "remote" | "merge" | "script";
+  type?: "local" | "remote" | "merge" | "script" | "aggregate";
   name?: string;
   desc?: string;
   file?: string;
   url?: string;
   updated?: number;
+  etag?: string;
+  last_modified?: string;
+  transport?: "direct" | "self_proxy" | "system_proxy";
   selected?: {
     name?: string;
     now?: string;
@@ -136,6 +139,18 @@ interface IProfileItem {
     expire: number;
   };
   option?: IProfileOption;
+  aggregate?: IProfileAggregate;
+  chain?: string[];
+}
+
+interface IProfileAggregate {
+  sources: string[];
+  prefix_name?: boolean;
+  dedupe?: boolean;
+  region_groups?: {
+    name: string;
+    pattern: string;
+  }[];
 }
 
 interface IProfileOption {
@@ -143,15 +158,121 @@ interface IProfileOption {
   with_proxy?: boolean;
   self_proxy?: boolean;
   update_interval?: number;
+  retry_times?: number;
+  connect_timeout?: number;
+  timeout?: number;
+  include?: string;
+  exclude?: string;
+  rename?: { pattern: string; replacement: string }[];
+  add_flag?: boolean;
+  headers?: Record<string, string>;
+  bearer_token?: string;
+  basic_auth?: { username: string; password?: string };
+  ca_cert?: string;
+  client_cert?: string;
+}
+
+interface IValidationError {
+  kind:
+    | "duplicate_name"
+    | "missing_member"
+    | "undefined_policy"
+    | "malformed_rule"
+    | "cyclic_group";
+  message: string;
+  source?: string;
+}
+
+interface IEnhanceStage {
+  kind:
+    | "profile"
+    | "filter"
+    | "merge"
+    | "script"
+    | "handle_fields"
+    | "builtin"
+    | "tun"
+    | "sort";
+  uid?: string;
+  diff: {
+    added: string[];
+    removed: string[];
+    modified: string[];
+    patch: string;
+  };
+}
+
+interface IEnhanceTrace {
+  stages: IEnhanceStage[];
+  config: Record<string, any>;
+  logs: Record<string, [string, string][]>;
+  errors: IValidationError[];
+}
+
+interface IExportResult {
+  data: string;
+  skipped: {
+    kind: "proxy" | "proxy-group" | "rule";
+    name: string;
+    reason: string;
+  }[];
+}
+
+interface IProfileRevision {
+  id: number;
+  uid: string;
+  source: "remote_update" | "manual_edit" | "import" | "rollback";
+  size: number;
+}
+
+interface IProfileNamedChanges {
+  added: string[];
+  removed: string[];
+  modified: string[];
+}
+
+interface IProfileChanges {
+  proxies: IProfileNamedChanges;
+  proxy_groups: IProfileNamedChanges;
+  rules: {
+    before: number;
+    after: number;
+    added: number;
+    removed: number;
+  };
 }
 
 interface IProfilesConfig {
+  version?: number;
   current?: string;
   chain?: string[];
+  chain_order?: "before" | "after";
   valid?: string[];
   items?: IProfileItem[];
 }
 
+interface IProfileSwitchRule {
+  name?: string;
+  gateway?: string;
+  interface?: string;
+  cidr?: string;
+  profile?: string;
+  mode?: "rule" | "global" | "direct" | "script";
+}
+
+interface IScheduledAction {
+  id: string;
+  name?: string;
+  cron: string;
+  action:
+    | { type: "change_mode"; mode: "rule" | "global" | "direct" | "script" }
+    | { type: "enable_system_proxy" }
+    | { type: "disable_system_proxy" }
+    | { type: "update_profiles" }
+    | { type: "restart_core" };
+  enabled?: boolean;
+}
+
 interface IVergeConfig {
   app_log_level?: "trace" | "debug" | "info" | "warn" | "error" | string;
   language?: string;
@@ -166,6 +287,7 @@ interface IVergeConfig {
   enable_auto_launch?: boolean;
   enable_service_mode?: boolean;
   enable_silent_start?: boolean;
+  enable_encrypt_secrets?: boolean;
   enable_system_proxy?: boolean;
   enable_random_port?: boolean;
   verge_mixed_port?: number;
@@ -194,6 +316,21 @@ interface IVergeConfig {
   enable_builtin_enhanced?: boolean;
   proxy_layout_column?: number;
   clash_tray_selector?: boolean;
+  max_profile_revisions?: number;
+  traffic_alert_percent?: number;
+  expire_alert_days?: number;
+  webdav_url?: string;
+  webdav_username?: string;
+  webdav_password?: string;
+  webdav_sync_passphrase?: string;
+  webdav_sync_interval?: number;
+  webdav_sync_exclude?: string[];
+  enable_profile_switch?: boolean;
+  profile_switch_rules?: IProfileSwitchRule[];
+  profile_switch_cooldown?: number;
+  scheduled_actions?: IScheduledAction[];
+  script_timeout?: number;
+  script_memory_limit?: number;
 
   clash_strategy?: {
     external_controller_port_strategy: "fixed" | "random" | "allow_fallback";